@@ -37,6 +37,33 @@ const CONTRACT_ID = "CCCG5JBZLW2CGYE62OWHM3VPKO3B6GCKUN2KIXG6T644BOW7PAM6LOKJ";
 const USDC_ASSET = "CBIELTK6YBZJU5UP2WWQEUCYKLPU6AUNZ2BQ4WWFEIE3USCIHMXQDAMA";
 const server = new SorobanRpc.Server("https://soroban-testnet.stellar.org:443");
 const networkPassphrase = Networks.TESTNET;
+// Largest `limit` the contract's page queries accept.
+const MAX_PAGE_SIZE = 50;
+
+type EscrowStatus =
+  | "Pending"
+  | "Approved"
+  | "Claimed"
+  | "Refunded"
+  | "PartiallyClaimed"
+  | "Disputed"
+  | "Resolved"
+  | "Cancelled";
+
+const STATUS_LABELS: Record<EscrowStatus, string> = {
+  Pending: "Pending",
+  Approved: "Approved",
+  Claimed: "Completed",
+  Refunded: "Refunded",
+  PartiallyClaimed: "Partially Claimed",
+  Disputed: "Disputed",
+  Resolved: "Resolved",
+  Cancelled: "Cancelled",
+};
+
+// The contract's `EscrowStatus` enum decodes as a one-element array, e.g. ["Pending"].
+const decodeStatus = (status: unknown): EscrowStatus =>
+  (Array.isArray(status) ? status[0] : status) as EscrowStatus;
 
 export default function EscrowPage() {
   const { address: connectedAddress, walletsKit, setAddress } = useWallet();
@@ -62,10 +89,10 @@ export default function EscrowPage() {
     deadlineDays: "7",
   });
 
-  const getStatus = (status: number) => {
-    const map = ["Pending", "Approved", "Completed", "Refunded"];
-    return map[status] || "Unknown";
-  };
+  const getStatus = (status: EscrowStatus) => STATUS_LABELS[status] || "Unknown";
+
+  const isClaimable = (status: EscrowStatus) =>
+    status === "Approved" || status === "PartiallyClaimed";
 
   // --- CONTRACT READS ---
 
@@ -77,23 +104,38 @@ export default function EscrowPage() {
 
       const account = await server.getAccount(connectedAddress);
 
+      // Walks the contract's paginated index, MAX_PAGE_SIZE ids at a time.
       const fetchIds = async (method: string) => {
-        const tx = new TransactionBuilder(account, {
-          fee: "1000",
-          networkPassphrase,
-        })
-          .addOperation(contract.call(method, userScVal))
-          .setTimeout(30)
-          .build();
-        const sim = await server.simulateTransaction(tx);
-        return SorobanRpc.Api.isSimulationSuccess(sim)
-          ? scValToNative(sim.result!.retval)
-          : [];
+        const ids: bigint[] = [];
+        let cursor: number | null = 0;
+        while (cursor !== null) {
+          const tx = new TransactionBuilder(account, {
+            fee: "1000",
+            networkPassphrase,
+          })
+            .addOperation(
+              contract.call(
+                method,
+                userScVal,
+                nativeToScVal(cursor, { type: "u32" }),
+                nativeToScVal(MAX_PAGE_SIZE, { type: "u32" })
+              )
+            )
+            .setTimeout(30)
+            .build();
+          const sim = await server.simulateTransaction(tx);
+          if (!SorobanRpc.Api.isSimulationSuccess(sim)) break;
+
+          const page = scValToNative(sim.result!.retval);
+          for (const [id] of page.entries) ids.push(id);
+          cursor = page.next_cursor ?? null;
+        }
+        return ids;
       };
 
       const [created, received] = await Promise.all([
-        fetchIds("get_created_ids"),
-        fetchIds("get_received_ids"),
+        fetchIds("get_created_page"),
+        fetchIds("get_received_page"),
       ]);
 
       setUserCreatedIds(created);
@@ -126,7 +168,7 @@ export default function EscrowPage() {
       const sim = await server.simulateTransaction(tx);
       if (SorobanRpc.Api.isSimulationSuccess(sim)) {
         const data = scValToNative(sim.result!.retval);
-        setEscrowDetail(data);
+        setEscrowDetail({ ...data, status: decodeStatus(data.status) });
         setEscrowId(targetId);
       } else {
         setEscrowDetail(null);
@@ -221,7 +263,7 @@ export default function EscrowPage() {
         // Handle ID-based actions (Approve, Claim, Refund)
         const idScVal = nativeToScVal(BigInt(escrowId), { type: "u64" });
         ops =
-          method === "claim"
+          method === "claim" || method === "approve"
             ? contract.call(
                 method,
                 idScVal,
@@ -448,7 +490,7 @@ export default function EscrowPage() {
                     <div className="text-right">
                       <div
                         className={`px-4 py-1 rounded-full text-xs font-black uppercase border ${
-                          escrowDetail.status === 1
+                          isClaimable(escrowDetail.status)
                             ? "bg-emerald-500/10 text-emerald-400 border-emerald-500/20"
                             : "bg-zinc-800 text-zinc-400 border-zinc-700"
                         }`}
@@ -498,7 +540,7 @@ export default function EscrowPage() {
                         <p className="text-xs font-bold text-zinc-400">
                           Lock Mechanism
                         </p>
-                        {escrowDetail.status === 1 ? (
+                        {isClaimable(escrowDetail.status) ? (
                           <Unlock size={14} className="text-emerald-500" />
                         ) : (
                           <Lock size={14} className="text-amber-500" />
@@ -516,13 +558,15 @@ export default function EscrowPage() {
                       {/* Dynamic Status Helper Text */}
                       <div className="pt-2 border-t border-zinc-800/50">
                         <p className="text-[9px] font-black text-zinc-600 uppercase tracking-tighter">
-                          {escrowDetail.status === 0 &&
+                          {escrowDetail.status === "Pending" &&
                           Math.floor(Date.now() / 1000) <
                             Number(escrowDetail.deadline)
                             ? "Waiting for time-lock to expire..."
-                            : escrowDetail.status === 0
+                            : escrowDetail.status === "Pending"
                             ? "Pending sender approval..."
-                            : "Funds available for claim"}
+                            : isClaimable(escrowDetail.status)
+                            ? "Funds available for claim"
+                            : getStatus(escrowDetail.status)}
                         </p>
                       </div>
                     </div>
@@ -530,7 +574,7 @@ export default function EscrowPage() {
 
                   <div className="p-4 md:p-6 bg-zinc-950/50 flex flex-wrap gap-4">
                     {/* APPROVE */}
-                    {escrowDetail.status === 0 &&
+                    {escrowDetail.status === "Pending" &&
                       escrowDetail.sender === connectedAddress &&
                       Math.floor(Date.now() / 1000) >=
                         Number(escrowDetail.deadline) && (
@@ -543,9 +587,10 @@ export default function EscrowPage() {
                       )}
 
                     {/* CLAIM */}
-                    {escrowDetail.status === 1 &&
+                    {isClaimable(escrowDetail.status) &&
                       escrowDetail.recipients.some(
-                        (r: any) => r[0] === connectedAddress
+                        (r: any, i: number) =>
+                          r[0] === connectedAddress && !escrowDetail.claimed[i]
                       ) && (
                         <button
                           onClick={() => handleAction("claim")}
@@ -556,7 +601,8 @@ export default function EscrowPage() {
                       )}
 
                     {/* REFUND */}
-                    {(escrowDetail.status === 0 || escrowDetail.status === 1) &&
+                    {(escrowDetail.status === "Pending" ||
+                      isClaimable(escrowDetail.status)) &&
                       escrowDetail.sender === connectedAddress && (
                         <button
                           onClick={() => handleAction("refund")}
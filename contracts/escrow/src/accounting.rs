@@ -0,0 +1,54 @@
+use soroban_sdk::{token, Address, Env};
+
+use crate::{fees, storage, DataKey, EscrowError};
+
+/// Sum of the funds live escrows hold in `token`.
+pub(crate) fn locked(env: &Env, token: &Address) -> i128 {
+    storage::get_persistent(env, &DataKey::Locked(token.clone())).unwrap_or(0)
+}
+
+fn set_locked(env: &Env, token: &Address, amount: i128) {
+    storage::set_persistent(env, &DataKey::Locked(token.clone()), &amount);
+}
+
+/// Pulls `amount` from `from` and returns what the contract actually
+/// received, which is less for fee-on-transfer tokens.
+pub(crate) fn deposit(
+    env: &Env,
+    token: &Address,
+    from: &Address,
+    amount: i128,
+) -> Result<i128, EscrowError> {
+    let contract = env.current_contract_address();
+    let client = token::Client::new(env, token);
+
+    let before = client.balance(&contract);
+    client.transfer(from, &contract, &amount);
+    let after = client.balance(&contract);
+
+    let received = after.checked_sub(before).ok_or(EscrowError::Overflow)?;
+    if received <= 0 {
+        return Err(EscrowError::NonPositiveAmount);
+    }
+
+    let total = locked(env, token)
+        .checked_add(received)
+        .ok_or(EscrowError::Overflow)?;
+    // Escrowed funds plus withheld fees must be backed by the held balance.
+    if total.saturating_add(fees::accrued(env, token)) > after {
+        return Err(EscrowError::Undercollateralized);
+    }
+    set_locked(env, token, total);
+    Ok(received)
+}
+
+/// Counts `amount` already held for an escrow funded before this accounting
+/// existed.
+pub(crate) fn adopt(env: &Env, token: &Address, amount: i128) {
+    set_locked(env, token, locked(env, token) + amount);
+}
+
+/// Records `amount` leaving escrow, whether paid out or moved to accrued fees.
+pub(crate) fn release(env: &Env, token: &Address, amount: i128) {
+    set_locked(env, token, locked(env, token) - amount);
+}
@@ -0,0 +1,66 @@
+use soroban_sdk::{contracttype, Address, Env};
+
+use crate::{storage, DataKey, EscrowError};
+
+pub(crate) fn has_admin(env: &Env) -> bool {
+    env.storage().instance().has(&DataKey::Admin)
+}
+
+pub(crate) fn get_admin(env: &Env) -> Result<Address, EscrowError> {
+    storage::extend_instance(env);
+    env.storage()
+        .instance()
+        .get(&DataKey::Admin)
+        .ok_or(EscrowError::NotInitialized)
+}
+
+pub(crate) fn set_admin(env: &Env, admin: &Address) {
+    env.storage().instance().set(&DataKey::Admin, admin);
+}
+
+/// Loads the admin and requires its authorization.
+pub(crate) fn require_admin(env: &Env) -> Result<Address, EscrowError> {
+    let admin = get_admin(env)?;
+    admin.require_auth();
+    Ok(admin)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Role {
+    Pauser,
+    FeeManager,
+}
+
+pub(crate) fn get_role(env: &Env, role: Role) -> Option<Address> {
+    env.storage().instance().get(&DataKey::Role(role))
+}
+
+pub(crate) fn set_role(env: &Env, role: Role, account: &Option<Address>) {
+    match account {
+        Some(account) => env.storage().instance().set(&DataKey::Role(role), account),
+        None => env.storage().instance().remove(&DataKey::Role(role)),
+    }
+}
+
+/// Requires authorization from the holder of `role`, falling back to the
+/// admin while the role is unassigned.
+pub(crate) fn require_role(env: &Env, role: Role) -> Result<Address, EscrowError> {
+    let holder = match get_role(env, role) {
+        Some(holder) => holder,
+        None => get_admin(env)?,
+    };
+    holder.require_auth();
+    Ok(holder)
+}
+
+pub(crate) fn get_pending_admin(env: &Env) -> Option<Address> {
+    env.storage().instance().get(&DataKey::PendingAdmin)
+}
+
+pub(crate) fn set_pending_admin(env: &Env, candidate: &Option<Address>) {
+    match candidate {
+        Some(candidate) => env.storage().instance().set(&DataKey::PendingAdmin, candidate),
+        None => env.storage().instance().remove(&DataKey::PendingAdmin),
+    }
+}
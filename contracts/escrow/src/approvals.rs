@@ -0,0 +1,73 @@
+use soroban_sdk::{contractclient, Address, Env};
+
+use crate::{EscrowData, EscrowError, EscrowStatus};
+
+/// Interface an escrow's oracle contract must implement.
+#[allow(dead_code)]
+#[contractclient(name = "OracleClient")]
+pub trait Oracle {
+    /// Whether the release condition of escrow `id` has been met.
+    fn is_fulfilled(env: Env, id: u64) -> bool;
+}
+
+/// Checks the approvers and oracle requested at creation. These apply to
+/// single-release escrows only, and an approver set excludes a lone approver.
+pub(crate) fn validate(data: &EscrowData) -> Result<(), EscrowError> {
+    if (data.approver.is_some() || data.oracle.is_some())
+        && (!data.is_single_release() || data.hashlock.is_some())
+    {
+        return Err(EscrowError::InvalidState);
+    }
+    if data.approvers.is_empty() {
+        if data.threshold != 0 {
+            return Err(EscrowError::BadThreshold);
+        }
+        return Ok(());
+    }
+    if !data.is_single_release() || data.hashlock.is_some() || data.approver.is_some() {
+        return Err(EscrowError::InvalidState);
+    }
+    if data.threshold == 0 || data.threshold > data.approvers.len() {
+        return Err(EscrowError::BadThreshold);
+    }
+    for (i, approver) in data.approvers.iter().enumerate() {
+        if data.approvers.iter().skip(i + 1).any(|other| other == approver) {
+            return Err(EscrowError::DuplicateApprover);
+        }
+    }
+    Ok(())
+}
+
+fn position(data: &EscrowData, approver: &Address) -> Result<u32, EscrowError> {
+    if data.status != EscrowStatus::Pending {
+        return Err(EscrowError::InvalidState);
+    }
+    data.approvers
+        .first_index_of(approver)
+        .ok_or(EscrowError::NotApprover)
+}
+
+pub(crate) fn count(data: &EscrowData) -> u32 {
+    data.approvals.iter().filter(|approved| *approved).count() as u32
+}
+
+/// Records `approver`'s approval and returns the number collected so far.
+pub(crate) fn record(data: &mut EscrowData, approver: &Address) -> Result<u32, EscrowError> {
+    let index = position(data, approver)?;
+    if data.approvals.get_unchecked(index) {
+        return Err(EscrowError::InvalidState);
+    }
+    data.approvals.set(index, true);
+    Ok(count(data))
+}
+
+/// Withdraws `approver`'s approval. Only possible while the threshold is
+/// unmet, since meeting it approves the escrow.
+pub(crate) fn revoke(data: &mut EscrowData, approver: &Address) -> Result<u32, EscrowError> {
+    let index = position(data, approver)?;
+    if !data.approvals.get_unchecked(index) {
+        return Err(EscrowError::InvalidState);
+    }
+    data.approvals.set(index, false);
+    Ok(count(data))
+}
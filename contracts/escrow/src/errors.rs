@@ -0,0 +1,52 @@
+use soroban_sdk::contracterror;
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum EscrowError {
+    NotFound            = 1,
+    NotRecipient        = 2,
+    NotApproved         = 3,
+    InvalidState        = 4,
+    DeadlinePassed      = 5,
+    DeadlineNotPassed   = 6,
+    BadPercentages      = 7,
+    NonPositiveAmount   = 8,
+    NoRecipients        = 9,
+    InvalidPageSize     = 10,
+    BadShares           = 11,
+    ZeroShare           = 12,
+    DuplicateRecipient  = 13,
+    AlreadyClaimed      = 14,
+    Overflow            = 15,
+    NoMilestones        = 16,
+    MilestoneNotFound   = 17,
+    MilestoneOutOfOrder = 18,
+    NothingToClaim      = 19,
+    NoArbiter           = 20,
+    InvalidArbiter      = 21,
+    NotParticipant      = 22,
+    Disputed            = 23,
+    NotInitialized      = 24,
+    AlreadyInitialized  = 25,
+    FeeTooHigh          = 26,
+    NoPendingAdmin      = 27,
+    Paused              = 28,
+    TokenNotAllowed     = 29,
+    AmountBelowMin      = 30,
+    AmountAboveMax      = 31,
+    BadTokenLimits      = 32,
+    Undercollateralized = 33,
+    DepositShortfall    = 34,
+    ExceedsEscrow       = 35,
+    InvalidDeadline     = 36,
+    NotAccepted         = 37,
+    BadPreimage         = 38,
+    BadSchedule         = 39,
+    BadThreshold        = 40,
+    DuplicateApprover   = 41,
+    NotApprover         = 42,
+    NotFulfilled        = 43,
+    ReleasedOnExpiry    = 44,
+    NotDeployer         = 45,
+}
@@ -1,4 +1,6 @@
-use soroban_sdk::{symbol_short, Address, Env, Vec};
+use soroban_sdk::{symbol_short, Address, BytesN, Env, Vec};
+
+use crate::{EscrowError, FeeConfig, PauseFlags, Role, TokenLimits};
 
 pub fn created(env: &Env, id: u64, sender: Address, amount: i128, deadline: u64) {
     env.events().publish(
@@ -7,10 +9,24 @@ pub fn created(env: &Env, id: u64, sender: Address, amount: i128, deadline: u64)
     );
 }
 
-pub fn approved(env: &Env, id: u64, sender: Address) {
+pub fn approved(env: &Env, id: u64, approver: Address) {
     env.events().publish(
         (symbol_short!("escrow"), symbol_short!("approved"), id),
-        sender,
+        approver,
+    );
+}
+
+pub fn approval_recorded(env: &Env, id: u64, approver: Address, count: u32) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("approval"), id),
+        (approver, count),
+    );
+}
+
+pub fn approval_revoked(env: &Env, id: u64, approver: Address, count: u32) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("revoked"), id),
+        (approver, count),
     );
 }
 
@@ -28,9 +44,184 @@ pub fn claimed(env: &Env, id: u64, recipients: Vec<Address>) {
     );
 }
 
+pub fn preimage_revealed(env: &Env, id: u64, recipient: Address, preimage: BytesN<32>) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("preimage"), id),
+        (recipient, preimage),
+    );
+}
+
+pub fn vested_withdrawn(env: &Env, id: u64, recipient: Address, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("vestwd"), id),
+        (recipient, amount),
+    );
+}
+
+pub fn vesting_stopped(env: &Env, id: u64, sender: Address, unvested: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("veststop"), id),
+        (sender, unvested),
+    );
+}
+
 pub fn refunded(env: &Env, id: u64, sender: Address) {
     env.events().publish(
         (symbol_short!("escrow"), symbol_short!("refunded"), id),
         sender,
     );
-}
\ No newline at end of file
+}
+
+pub fn cancelled(env: &Env, id: u64, sender: Address, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("cancelled"), id),
+        (sender, amount),
+    );
+}
+
+pub fn objected(env: &Env, id: u64, sender: Address) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("objected"), id),
+        sender,
+    );
+}
+
+pub fn waived(env: &Env, id: u64, recipient: Address, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("waived"), id),
+        (recipient, amount),
+    );
+}
+
+pub fn accepted(env: &Env, id: u64, recipient: Address) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("accepted"), id),
+        recipient,
+    );
+}
+
+pub fn rejected(env: &Env, id: u64, recipient: Address, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("rejected"), id),
+        (recipient, amount),
+    );
+}
+
+pub fn dispute_raised(env: &Env, id: u64, caller: Address) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("disputed"), id),
+        caller,
+    );
+}
+
+pub fn dispute_resolved(env: &Env, id: u64, arbiter: Address, payouts: Vec<(Address, i128)>) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("resolved"), id),
+        (arbiter, payouts),
+    );
+}
+
+pub fn deposited(env: &Env, id: u64, amount: i128, total: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("deposit"), id),
+        (amount, total),
+    );
+}
+
+pub fn reduced(env: &Env, id: u64, amount: i128, total: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("reduced"), id),
+        (amount, total),
+    );
+}
+
+pub fn deadline_changed(env: &Env, id: u64, previous: u64, deadline: u64) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("deadline"), id),
+        (previous, deadline),
+    );
+}
+
+pub fn admin_proposed(env: &Env, admin: Address, candidate: Address) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("admprop")),
+        (admin, candidate),
+    );
+}
+
+pub fn admin_changed(env: &Env, previous: Address, admin: Address) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("admset")),
+        (previous, admin),
+    );
+}
+
+pub fn role_changed(env: &Env, role: Role, account: Option<Address>) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("roleset")),
+        (role, account),
+    );
+}
+
+pub fn upgraded(env: &Env, wasm_hash: BytesN<32>) {
+    env.events().publish((symbol_short!("escrow"), symbol_short!("upgraded")), wasm_hash);
+}
+
+pub fn migration_skipped(env: &Env, id: u64, err: EscrowError) {
+    env.events().publish((symbol_short!("escrow"), symbol_short!("migskip"), id), err);
+}
+
+pub fn migrated(env: &Env, version: u32) {
+    env.events().publish((symbol_short!("escrow"), symbol_short!("migrated")), version);
+}
+
+pub fn fee_config_set(env: &Env, config: FeeConfig) {
+    env.events().publish((symbol_short!("escrow"), symbol_short!("feeset")), config);
+}
+
+pub fn pause_changed(env: &Env, flags: PauseFlags) {
+    env.events().publish((symbol_short!("escrow"), symbol_short!("pause")), flags);
+}
+
+pub fn token_listed(env: &Env, token: Address, limits: TokenLimits) {
+    env.events().publish((symbol_short!("escrow"), symbol_short!("tokenset"), token), limits);
+}
+
+pub fn token_delisted(env: &Env, token: Address) {
+    env.events().publish((symbol_short!("escrow"), symbol_short!("tokenrm"), token), ());
+}
+
+pub fn fee_collected(env: &Env, id: u64, token: Address, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("fee"), id),
+        (token, amount),
+    );
+}
+
+pub fn fees_withdrawn(env: &Env, token: Address, treasury: Address, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("feewithdr")),
+        (token, treasury, amount),
+    );
+}
+
+pub fn milestone_released(env: &Env, id: u64, index: u32, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("msrelease"), id),
+        (index, amount),
+    );
+}
+
+pub fn milestone_claimed(env: &Env, id: u64, index: u32, recipient: Address, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("msclaimed"), id),
+        (index, recipient, amount),
+    );
+}
+
+pub fn milestone_refunded(env: &Env, id: u64, index: u32, amount: i128) {
+    env.events().publish(
+        (symbol_short!("escrow"), symbol_short!("msrefund"), id),
+        (index, amount),
+    );
+}
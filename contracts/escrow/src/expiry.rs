@@ -0,0 +1,53 @@
+use soroban_sdk::{contracttype, Env};
+
+use crate::{EscrowData, EscrowError, EscrowStatus};
+
+/// What happens to an unapproved escrow once its deadline passes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub enum ReleasePolicy {
+    /// The sender may refund; recipients can no longer claim.
+    #[default]
+    RefundOnExpiry,
+    /// Recipients may claim without approval, unless the sender objected
+    /// before the deadline.
+    ReleaseOnExpiry,
+}
+
+/// Release on expiry needs a deadline and applies to single-release escrows
+/// only.
+pub(crate) fn validate(data: &EscrowData) -> Result<(), EscrowError> {
+    if data.on_expiry == ReleasePolicy::RefundOnExpiry {
+        return Ok(());
+    }
+    if !data.is_single_release() || data.hashlock.is_some() {
+        return Err(EscrowError::InvalidState);
+    }
+    if data.deadline == 0 {
+        return Err(EscrowError::InvalidDeadline);
+    }
+    Ok(())
+}
+
+/// Whether the deadline has released the escrow to its recipients.
+pub(crate) fn is_released(env: &Env, data: &EscrowData) -> bool {
+    data.on_expiry == ReleasePolicy::ReleaseOnExpiry
+        && !data.objected
+        && data.is_accepted()
+        && env.ledger().timestamp() > data.deadline
+}
+
+/// Records the sender's objection, which restores refund-on-expiry.
+pub(crate) fn object(env: &Env, data: &mut EscrowData) -> Result<(), EscrowError> {
+    if data.on_expiry != ReleasePolicy::ReleaseOnExpiry
+        || data.status != EscrowStatus::Pending
+        || data.objected
+    {
+        return Err(EscrowError::InvalidState);
+    }
+    if env.ledger().timestamp() > data.deadline {
+        return Err(EscrowError::DeadlinePassed);
+    }
+    data.objected = true;
+    Ok(())
+}
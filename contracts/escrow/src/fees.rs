@@ -0,0 +1,79 @@
+use soroban_sdk::{contracttype, Address, Env};
+
+use crate::{events, split, storage, DataKey, EscrowData, EscrowError, BPS_DENOMINATOR};
+
+/// Highest protocol fee the admin may configure (10%).
+pub const MAX_FEE_BPS: u32 = 1_000;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FeeConfig {
+    pub fee_bps:       u32,
+    pub treasury:      Address,
+    /// Also charge the fee on refunds returned to the sender.
+    pub fee_on_refund: bool,
+}
+
+pub(crate) fn get_config(env: &Env) -> Option<FeeConfig> {
+    env.storage().instance().get(&DataKey::FeeConfig)
+}
+
+pub(crate) fn set_config(env: &Env, config: &FeeConfig) -> Result<(), EscrowError> {
+    if config.fee_bps > MAX_FEE_BPS {
+        return Err(EscrowError::FeeTooHigh);
+    }
+    env.storage().instance().set(&DataKey::FeeConfig, config);
+    Ok(())
+}
+
+pub(crate) fn accrued(env: &Env, token: &Address) -> i128 {
+    storage::get_persistent(env, &DataKey::FeesAccrued(token.clone())).unwrap_or(0)
+}
+
+fn set_accrued(env: &Env, token: &Address, amount: i128) {
+    storage::set_persistent(env, &DataKey::FeesAccrued(token.clone()), &amount);
+}
+
+/// Copies the current fee terms onto a new escrow, so later config changes
+/// leave it alone.
+pub(crate) fn snapshot(env: &Env, data: &mut EscrowData) {
+    if let Some(config) = get_config(env) {
+        data.fee_bps = config.fee_bps;
+        data.refund_fee = config.fee_on_refund;
+    }
+}
+
+/// Withholds the escrow's protocol fee from a claim payout and returns the net
+/// amount.
+pub(crate) fn deduct_on_claim(env: &Env, id: u64, data: &EscrowData, gross: i128) -> i128 {
+    deduct(env, id, &data.token, gross, data.fee_bps)
+}
+
+/// Withholds the escrow's protocol fee from a refund if it was created with
+/// `fee_on_refund`.
+pub(crate) fn deduct_on_refund(env: &Env, id: u64, data: &EscrowData, gross: i128) -> i128 {
+    if !data.refund_fee {
+        return gross;
+    }
+    deduct(env, id, &data.token, gross, data.fee_bps)
+}
+
+fn deduct(env: &Env, id: u64, token: &Address, gross: i128, fee_bps: u32) -> i128 {
+    let fee = split::mul_div(gross, fee_bps as u64, BPS_DENOMINATOR as u64);
+    if fee == 0 {
+        return gross;
+    }
+
+    set_accrued(env, token, accrued(env, token) + fee);
+    events::fee_collected(env, id, token.clone(), fee);
+    gross - fee
+}
+
+/// Zeroes the accrued balance for `token` and returns it.
+pub(crate) fn take_accrued(env: &Env, token: &Address) -> i128 {
+    let amount = accrued(env, token);
+    if amount > 0 {
+        set_accrued(env, token, 0);
+    }
+    amount
+}
@@ -1,20 +1,274 @@
 #![no_std]
-use soroban_sdk::{contract, contractimpl, contracttype, token, Address, Env, Vec};
+// The client generated for `create_vesting` inherits its argument count.
+#![allow(clippy::too_many_arguments)]
+use soroban_sdk::{contract, contractimpl, contracttype, token, Address, BytesN, Env, Vec};
 
+mod accounting;
+mod admin;
+mod approvals;
+mod errors;
 mod events;
+mod expiry;
+mod fees;
+mod milestones;
+mod pause;
+mod split;
+mod status;
+mod storage;
+mod tokens;
+mod upgrade;
+mod vesting;
+
+pub use admin::Role;
+pub use errors::EscrowError;
+pub use expiry::ReleasePolicy;
+pub use fees::{FeeConfig, MAX_FEE_BPS};
+pub use milestones::{Milestone, MilestoneStatus};
+pub use pause::PauseFlags;
+pub use status::EscrowStatus;
+pub use storage::INDEX_MIGRATION_CHUNK;
+pub use tokens::TokenLimits;
+pub use upgrade::{LEGACY_SCHEMA_VERSION, MIGRATION_BATCH_SIZE, SCHEMA_VERSION};
+pub use vesting::Vesting;
+
+use storage::UserIndex;
+
+/// Upper bound on `limit` for the paginated index queries.
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+/// Recipient shares are stored in basis points and must sum to this value.
+pub const BPS_DENOMINATOR: u32 = 10_000;
 
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
     Escrow(u64),
     NextId,
+    /// Legacy single-vector index, migrated on the next write.
     UserCreated(Address),
+    /// Legacy single-vector index, migrated on the next write.
     UserReceived(Address),
+    UserCreatedCount(Address),
+    UserCreatedEntry(Address, u32),
+    UserReceivedCount(Address),
+    UserReceivedEntry(Address, u32),
+    /// Legacy entries already copied by an unfinished index migration.
+    UserCreatedCursor(Address),
+    /// Legacy entries already copied by an unfinished index migration.
+    UserReceivedCursor(Address),
+    Admin,
+    PendingAdmin,
+    Role(Role),
+    Pause,
+    SchemaVersion,
+    /// Last escrow id rewritten by an unfinished `migrate`.
+    MigrationCursor,
+    FeeConfig,
+    /// Allowlisted escrow tokens, in listing order.
+    SupportedTokens,
+    TokenLimits(Address),
+    /// Protocol fees withheld and not yet withdrawn, per token.
+    FeesAccrued(Address),
+    /// Funds held for live escrows, per token.
+    Locked(Address),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[contracttype]
 pub struct EscrowData {
+    pub sender:     Address,
+    /// Recipient shares in basis points.
+    pub recipients: Vec<(Address, u32)>,
+    pub amount:     i128,
+    pub token:      Address,
+    pub created_at: u64,
+    pub deadline:   u64,
+    pub status:     EscrowStatus,
+    /// Parallel to `recipients`: whether each share has been withdrawn.
+    pub claimed:    Vec<bool>,
+    /// Ordered release tranches; empty for single-release escrows.
+    pub milestones: Vec<Milestone>,
+    /// May settle disputes raised by the sender or a recipient.
+    pub arbiter:    Option<Address>,
+    /// Parallel to `recipients` when each must `accept` before approval;
+    /// empty otherwise.
+    pub accepted:   Vec<bool>,
+    /// SHA-256 of the preimage that releases a hash time-locked escrow.
+    pub hashlock:   Option<BytesN<32>>,
+    /// Linear release schedule of a streaming escrow; read it with `vesting()`.
+    /// Holds at most one entry, as the SDK cannot encode an `Option` of a
+    /// contract struct.
+    pub vesting:    Vec<Vesting>,
+    /// Sign off on release in place of the sender once `threshold` of them
+    /// approve; empty for sender-approved escrows.
+    pub approvers:  Vec<Address>,
+    pub threshold:  u32,
+    /// Parallel to `approvers`.
+    pub approvals:  Vec<bool>,
+    /// Approves in place of the sender, e.g. a delivery-tracking service.
+    pub approver:   Option<Address>,
+    /// Contract whose `is_fulfilled(id)` lets anyone `approve_via_oracle`.
+    pub oracle:     Option<Address>,
+    /// What an unapproved escrow does once its deadline passes.
+    pub on_expiry:  ReleasePolicy,
+    /// Set when the sender objects to release on expiry.
+    pub objected:   bool,
+    /// Protocol fee in force when the escrow was created.
+    pub fee_bps:    u32,
+    /// Whether refunds pay `fee_bps` too, as configured at creation.
+    pub refund_fee: bool,
+}
+
+/// Optional settings accepted by `create_v2` and `create_milestones`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct EscrowOptions {
+    pub arbiter:            Option<Address>,
+    /// Every recipient must `accept` before the escrow can be approved.
+    pub require_acceptance: bool,
+    /// M-of-N approval: `threshold` of `approvers` must approve instead of
+    /// the sender.
+    pub approvers:          Option<Vec<Address>>,
+    pub threshold:          u32,
+    /// Single third-party approver, who replaces the sender.
+    pub approver:           Option<Address>,
+    /// Contract implementing `is_fulfilled(id) -> bool`.
+    pub oracle:             Option<Address>,
+    pub on_expiry:          ReleasePolicy,
+}
+
+impl EscrowData {
+    pub fn new(
+        env: &Env,
+        sender: Address,
+        recipients: Vec<(Address, u32)>,
+        amount: i128,
+        token: Address,
+        deadline: u64,
+    ) -> EscrowData {
+        EscrowData {
+            sender,
+            claimed: flags(env, recipients.len(), false),
+            recipients,
+            amount,
+            token,
+            created_at: env.ledger().timestamp(),
+            deadline,
+            status: EscrowStatus::Pending,
+            milestones: Vec::new(env),
+            arbiter: None,
+            accepted: Vec::new(env),
+            hashlock: None,
+            vesting: Vec::new(env),
+            approvers: Vec::new(env),
+            threshold: 0,
+            approvals: Vec::new(env),
+            approver: None,
+            oracle: None,
+            on_expiry: ReleasePolicy::RefundOnExpiry,
+            objected: false,
+            fee_bps: 0,
+            refund_fee: false,
+        }
+    }
+
+    pub fn with_options(mut self, options: EscrowOptions) -> EscrowData {
+        self.arbiter = options.arbiter;
+        if options.require_acceptance {
+            self.accepted = flags(self.recipients.env(), self.recipients.len(), false);
+        }
+        if let Some(approvers) = options.approvers {
+            self.approvals = flags(approvers.env(), approvers.len(), false);
+            self.approvers = approvers;
+        }
+        self.threshold = options.threshold;
+        self.approver = options.approver;
+        self.oracle = options.oracle;
+        self.on_expiry = options.on_expiry;
+        self
+    }
+
+    /// Whether every recipient has accepted, or acceptance is not required.
+    pub fn is_accepted(&self) -> bool {
+        self.accepted.iter().all(|accepted| accepted)
+    }
+
+    fn recipient_index(&self, recipient: &Address) -> Result<u32, EscrowError> {
+        self.recipients
+            .iter()
+            .position(|(r, _)| r == *recipient)
+            .map(|index| index as u32)
+            .ok_or(EscrowError::NotRecipient)
+    }
+
+    pub fn is_participant(&self, address: &Address) -> bool {
+        self.sender == *address || self.recipients.iter().any(|(r, _)| r == *address)
+    }
+
+    /// Each recipient's share of `amount`, parallel to `recipients`.
+    pub fn shares(&self, env: &Env) -> Vec<(Address, i128)> {
+        split::split(env, self.amount, &self.recipients, BPS_DENOMINATOR)
+    }
+
+    pub fn recipient_addresses(&self, env: &Env) -> Vec<Address> {
+        let mut addresses = Vec::new(env);
+        for (recipient, _) in self.recipients.iter() {
+            addresses.push_back(recipient);
+        }
+        addresses
+    }
+
+    /// Sum of the shares not yet withdrawn by their recipient.
+    pub fn unclaimed_amount(&self, env: &Env) -> i128 {
+        let mut total = 0;
+        for (i, (_, share)) in self.shares(env).iter().enumerate() {
+            if !self.claimed.get_unchecked(i as u32) {
+                total += share;
+            }
+        }
+        total
+    }
+
+    /// Funds the contract still holds for this escrow. Finished escrows hold
+    /// nothing, whatever their per-recipient flags say.
+    pub fn locked_amount(&self, env: &Env) -> i128 {
+        if matches!(
+            self.status,
+            EscrowStatus::Claimed
+                | EscrowStatus::Refunded
+                | EscrowStatus::Resolved
+                | EscrowStatus::Cancelled
+        ) {
+            0
+        } else if !self.milestones.is_empty() {
+            milestones::locked_amount(env, self)
+        } else if let Some(vesting) = self.vesting() {
+            vesting::locked_amount(env, self, &vesting)
+        } else {
+            self.unclaimed_amount(env)
+        }
+    }
+
+    /// Whether the whole amount is released at once, rather than in tranches
+    /// or as a stream.
+    pub fn is_single_release(&self) -> bool {
+        self.milestones.is_empty() && self.vesting().is_none()
+    }
+
+    /// Release schedule, for streaming escrows.
+    pub fn vesting(&self) -> Option<Vesting> {
+        self.vesting.get(0)
+    }
+
+    pub(crate) fn set_vesting(&mut self, vesting: Vesting) {
+        self.vesting = Vec::from_array(self.vesting.env(), [vesting]);
+    }
+}
+
+/// Record layout written before `EscrowStatus` existed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct LegacyEscrowData {
     pub sender:     Address,
     pub recipients: Vec<(Address, u32)>,
     pub amount:     i128,
@@ -25,11 +279,232 @@ pub struct EscrowData {
     pub status:     u32,
 }
 
+impl LegacyEscrowData {
+    pub fn migrate(self, env: &Env) -> Result<EscrowData, EscrowError> {
+        let status = EscrowStatus::from_legacy(self.status)?;
+        let recipients = split::legacy_percentages_to_bps(env, &self.recipients)?;
+        let claimed = flags(env, recipients.len(), status == EscrowStatus::Claimed);
+
+        Ok(EscrowData {
+            sender:     self.sender,
+            recipients,
+            amount:     self.amount,
+            token:      self.token,
+            created_at: self.created_at,
+            deadline:   self.deadline,
+            status,
+            claimed,
+            milestones: Vec::new(env),
+            arbiter:    None,
+            accepted:   Vec::new(env),
+            hashlock:   None,
+            vesting:    Vec::new(env),
+            approvers:  Vec::new(env),
+            threshold:  0,
+            approvals:  Vec::new(env),
+            approver:   None,
+            oracle:     None,
+            on_expiry:  ReleasePolicy::RefundOnExpiry,
+            objected:   false,
+            fee_bps:    0,
+            refund_fee: false,
+        })
+    }
+}
+
+fn flags(env: &Env, len: u32, value: bool) -> Vec<bool> {
+    let mut flags = Vec::new(env);
+    for _ in 0..len {
+        flags.push_back(value);
+    }
+    flags
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct EscrowPage {
+    pub entries:     Vec<(u64, EscrowData)>,
+    pub next_cursor: Option<u32>,
+}
+
 #[contract]
 pub struct Escrow;
 
 #[contractimpl]
 impl Escrow {
+    /// Sets the admin. Only `deployer`, the account that deployed this
+    /// contract with `salt`, may call it, so nobody can slip in between
+    /// deployment and initialization.
+    pub fn initialize(
+        env: Env,
+        admin: Address,
+        deployer: Address,
+        salt: BytesN<32>,
+    ) -> Result<(), EscrowError> {
+        if admin::has_admin(&env) {
+            return Err(EscrowError::AlreadyInitialized);
+        }
+        let deployed = env.deployer().with_address(deployer.clone(), salt).deployed_address();
+        if deployed != env.current_contract_address() {
+            return Err(EscrowError::NotDeployer);
+        }
+        deployer.require_auth();
+        admin::set_admin(&env, &admin);
+        storage::extend_instance(&env);
+        Ok(())
+    }
+
+    pub fn get_admin(env: Env) -> Result<Address, EscrowError> {
+        admin::get_admin(&env)
+    }
+
+    /// Replaces the contract code, keeping its storage. Run `migrate` afterwards
+    /// if the new build bumps `SCHEMA_VERSION`.
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), EscrowError> {
+        admin::require_admin(&env)?;
+        env.deployer().update_current_contract_wasm(new_wasm_hash.clone());
+
+        events::upgraded(&env, new_wasm_hash);
+        Ok(())
+    }
+
+    /// Rewrites stored escrows in the current layout, a batch per call. Returns
+    /// `true` once the contract is at `SCHEMA_VERSION`.
+    pub fn migrate(env: Env) -> Result<bool, EscrowError> {
+        admin::require_admin(&env)?;
+        if upgrade::schema_version(&env) == SCHEMA_VERSION {
+            return Ok(true);
+        }
+
+        let done = upgrade::migrate_batch(&env)?;
+        if done {
+            events::migrated(&env, SCHEMA_VERSION);
+        }
+        Ok(done)
+    }
+
+    pub fn get_schema_version(env: Env) -> u32 {
+        upgrade::schema_version(&env)
+    }
+
+    /// First step of an admin transfer; `candidate` must call `accept_admin`.
+    pub fn propose_admin(env: Env, candidate: Address) -> Result<(), EscrowError> {
+        let admin = admin::require_admin(&env)?;
+        admin::set_pending_admin(&env, &Some(candidate.clone()));
+
+        events::admin_proposed(&env, admin, candidate);
+        Ok(())
+    }
+
+    pub fn accept_admin(env: Env) -> Result<(), EscrowError> {
+        let previous = admin::get_admin(&env)?;
+        let candidate = admin::get_pending_admin(&env).ok_or(EscrowError::NoPendingAdmin)?;
+        candidate.require_auth();
+
+        admin::set_admin(&env, &candidate);
+        admin::set_pending_admin(&env, &None);
+
+        events::admin_changed(&env, previous, candidate);
+        Ok(())
+    }
+
+    /// Assigns or, with `None`, clears a role. Unassigned roles fall back to the admin.
+    pub fn set_role(env: Env, role: Role, account: Option<Address>) -> Result<(), EscrowError> {
+        admin::require_admin(&env)?;
+        admin::set_role(&env, role, &account);
+
+        events::role_changed(&env, role, account);
+        Ok(())
+    }
+
+    pub fn get_role(env: Env, role: Role) -> Option<Address> {
+        admin::get_role(&env, role)
+    }
+
+    /// Sets the circuit-breaker flags. Callable by the pauser role.
+    pub fn set_pause(env: Env, flags: PauseFlags) -> Result<(), EscrowError> {
+        admin::require_role(&env, Role::Pauser)?;
+        pause::set_flags(&env, &flags);
+
+        events::pause_changed(&env, flags);
+        Ok(())
+    }
+
+    pub fn get_pause(env: Env) -> PauseFlags {
+        pause::get_flags(&env)
+    }
+
+    /// Adds `token` to the allowlist, or updates the limits of a listed token.
+    pub fn set_token(env: Env, token: Address, limits: TokenLimits) -> Result<(), EscrowError> {
+        admin::require_admin(&env)?;
+        tokens::set(&env, &token, &limits)?;
+
+        events::token_listed(&env, token, limits);
+        Ok(())
+    }
+
+    /// Delists `token`. Escrows already holding it can still settle.
+    pub fn remove_token(env: Env, token: Address) -> Result<(), EscrowError> {
+        admin::require_admin(&env)?;
+        tokens::remove(&env, &token)?;
+
+        events::token_delisted(&env, token);
+        Ok(())
+    }
+
+    pub fn get_supported_tokens(env: Env) -> Vec<Address> {
+        tokens::supported(&env)
+    }
+
+    pub fn get_token_limits(env: Env, token: Address) -> Option<TokenLimits> {
+        tokens::get_limits(&env, &token)
+    }
+
+    pub fn set_fee_config(
+        env: Env,
+        fee_bps: u32,
+        treasury: Address,
+        fee_on_refund: bool,
+    ) -> Result<(), EscrowError> {
+        admin::require_role(&env, Role::FeeManager)?;
+        let config = FeeConfig { fee_bps, treasury, fee_on_refund };
+        fees::set_config(&env, &config)?;
+
+        events::fee_config_set(&env, config);
+        Ok(())
+    }
+
+    pub fn get_fee_config(env: Env) -> Option<FeeConfig> {
+        fees::get_config(&env)
+    }
+
+    pub fn get_accrued_fees(env: Env, token: Address) -> i128 {
+        fees::accrued(&env, &token)
+    }
+
+    /// Total `token` funds owed to live escrows.
+    pub fn get_locked(env: Env, token: Address) -> i128 {
+        accounting::locked(&env, &token)
+    }
+
+    /// Sends every fee accrued in `token` to the configured treasury.
+    pub fn withdraw_fees(env: Env, token: Address) -> Result<i128, EscrowError> {
+        admin::require_role(&env, Role::FeeManager)?;
+        let config = fees::get_config(&env).ok_or(EscrowError::NotInitialized)?;
+
+        let amount = fees::take_accrued(&env, &token);
+        if amount > 0 {
+            let client = token::Client::new(&env, &token);
+            client.transfer(&env.current_contract_address(), &config.treasury, &amount);
+        }
+
+        events::fees_withdrawn(&env, token, config.treasury, amount);
+        Ok(amount)
+    }
+
+    /// Locks `amount` of `token` for `recipients`, whose shares are percentages.
+    /// The escrow records what the contract actually received, which is less
+    /// than `amount` for fee-on-transfer tokens.
     pub fn create(
         env: Env,
         sender: Address,
@@ -37,171 +512,831 @@ impl Escrow {
         amount: i128,
         token: Address,
         deadline: u64,
-    ) -> u64 {
+    ) -> Result<u64, EscrowError> {
+        sender.require_auth();
+        let recipients = split::percentages_to_bps(&env, &recipients)?;
+        Self::open(&env, EscrowData::new(&env, sender, recipients, amount, token, deadline))
+    }
+
+    /// Like `create`, but `recipients` shares are in basis points summing to
+    /// `BPS_DENOMINATOR`, and optional settings such as an arbiter are accepted.
+    pub fn create_v2(
+        env: Env,
+        sender: Address,
+        recipients: Vec<(Address, u32)>,
+        amount: i128,
+        token: Address,
+        deadline: u64,
+        options: EscrowOptions,
+    ) -> Result<u64, EscrowError> {
         sender.require_auth();
-        assert!(amount > 0, "amount must be positive");
-        assert!(!recipients.is_empty(), "at least one recipient required");
+        split::validate_bps(&recipients)?;
+        let data = EscrowData::new(&env, sender, recipients, amount, token, deadline);
+        Self::open(&env, data.with_options(options))
+    }
 
-        let total_percentage: u32 = recipients.iter().map(|(_, p)| p).sum();
-        assert!(total_percentage == 100, "percentages must sum to 100");
+    /// Creates an escrow released in ordered tranches. `milestones` holds
+    /// `(amount, deadline)` pairs, with a zero deadline meaning none; shares
+    /// are in basis points as for `create_v2`.
+    pub fn create_milestones(
+        env: Env,
+        sender: Address,
+        recipients: Vec<(Address, u32)>,
+        token: Address,
+        milestones: Vec<(i128, u64)>,
+        options: EscrowOptions,
+    ) -> Result<u64, EscrowError> {
+        sender.require_auth();
+        split::validate_bps(&recipients)?;
+        let (milestones, amount) = milestones::build(&env, &milestones, recipients.len())?;
 
-        let contract = env.current_contract_address();
-        let client = token::Client::new(&env, &token);
-        client.transfer(&sender, &contract, &amount);
+        let mut data = EscrowData::new(&env, sender, recipients, amount, token, 0);
+        data.milestones = milestones;
+        Self::open(&env, data.with_options(options))
+    }
 
-        let id = Self::next_id(&env);
+    /// Creates a hash time-locked escrow for atomic swaps. Revealing the
+    /// preimage of `hashlock` through `claim_with_preimage` pays `recipient`
+    /// until `timelock`; after it, the sender may `refund`.
+    pub fn create_htlc(
+        env: Env,
+        sender: Address,
+        recipient: Address,
+        amount: i128,
+        token: Address,
+        hashlock: BytesN<32>,
+        timelock: u64,
+    ) -> Result<u64, EscrowError> {
+        sender.require_auth();
+        if timelock == 0 {
+            return Err(EscrowError::InvalidDeadline);
+        }
 
-        let data = EscrowData {
-            sender: sender.clone(),
-            recipients: recipients.clone(),
-            amount,
-            token,
-            created_at: env.ledger().timestamp(),
-            deadline,
-            approved: false,
-            status: 0,
-        };
+        let recipients = Vec::from_array(&env, [(recipient, BPS_DENOMINATOR)]);
+        let mut data = EscrowData::new(&env, sender, recipients, amount, token, timelock);
+        data.hashlock = Some(hashlock);
+        Self::open(&env, data)
+    }
 
-        env.storage().instance().set(&DataKey::Escrow(id), &data);
+    /// Pays out a hash time-locked escrow to its recipient. Anyone holding the
+    /// preimage may call this; the preimage is published in an event so the
+    /// counterparty can complete the other leg of the swap. Preimages are
+    /// 32 bytes, as BIP-199 requires, so both chains of a swap accept them.
+    pub fn claim_with_preimage(
+        env: Env,
+        id: u64,
+        preimage: BytesN<32>,
+    ) -> Result<(), EscrowError> {
+        pause::check_claims(&env)?;
+
+        let mut data = storage::load_escrow(&env, id)?;
+        let hashlock = data.hashlock.clone().ok_or(EscrowError::InvalidState)?;
+        if env.ledger().timestamp() > data.deadline {
+            return Err(EscrowError::DeadlinePassed);
+        }
+        if env.crypto().sha256(&preimage.clone().into()).to_bytes() != hashlock {
+            return Err(EscrowError::BadPreimage);
+        }
+
+        // The preimage stands in for the sender's approval.
+        data.status = data
+            .status
+            .transition(EscrowStatus::Approved)?
+            .transition(EscrowStatus::Claimed)?;
+        data.claimed.set(0, true);
+        storage::save_escrow(&env, id, &data);
 
-        let sender_key = DataKey::UserCreated(sender);
-        let mut sender_list: Vec<u64> = env
-            .storage()
-            .instance()
-            .get(&sender_key)
-            .unwrap_or(Vec::new(&env));
-        sender_list.push_back(id);
-        env.storage().instance().set(&sender_key, &sender_list);
+        let (recipient, _) = data.recipients.get_unchecked(0);
+        accounting::release(&env, &data.token, data.amount);
+        let payout = fees::deduct_on_claim(&env, id, &data, data.amount);
+        let client = token::Client::new(&env, &data.token);
+        client.transfer(&env.current_contract_address(), &recipient, &payout);
 
-        for (recipient, _) in recipients.iter() {
-            let recipient_key = DataKey::UserReceived(recipient);
-            let mut recipient_list: Vec<u64> = env
-                .storage()
-                .instance()
-                .get(&recipient_key)
-                .unwrap_or(Vec::new(&env));
-            recipient_list.push_back(id);
-            env.storage()
-                .instance()
-                .set(&recipient_key, &recipient_list);
+        events::preimage_revealed(&env, id, recipient, preimage);
+        events::claimed(&env, id, data.recipient_addresses(&env));
+        Ok(())
+    }
+
+    /// Tops up a single-release escrow nobody has claimed from yet. Token
+    /// limits apply to the new total.
+    pub fn deposit_more(env: Env, id: u64, amount: i128) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
+
+        data.sender.require_auth();
+        Self::ensure_adjustable(&data)?;
+        pause::check_creation(&env)?;
+        if amount <= 0 {
+            return Err(EscrowError::NonPositiveAmount);
         }
+        let total = data.amount.checked_add(amount).ok_or(EscrowError::Overflow)?;
+        tokens::check(&env, &data.token, total)?;
 
-        events::created(&env, id, data.sender.clone(), amount, deadline);
+        let received = accounting::deposit(&env, &data.token, &data.sender, amount)?;
+        data.amount += received;
+        storage::save_escrow(&env, id, &data);
 
-        id
+        events::deposited(&env, id, received, data.amount);
+        Ok(())
     }
 
-    pub fn get_created_ids(env: Env, user: Address) -> Vec<u64> {
-        env.storage()
-            .instance()
-            .get(&DataKey::UserCreated(user))
-            .unwrap_or(Vec::new(&env))
+    /// Returns part of an escrow to the sender. Needs the sender and every
+    /// recipient to agree; `amount` must leave a positive balance.
+    pub fn reduce(env: Env, id: u64, amount: i128) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
+
+        data.sender.require_auth();
+        for recipient in data.recipient_addresses(&env).iter() {
+            recipient.require_auth();
+        }
+        Self::ensure_adjustable(&data)?;
+        pause::check_refunds(&env, false)?;
+        if amount <= 0 {
+            return Err(EscrowError::NonPositiveAmount);
+        }
+        if amount >= data.amount {
+            return Err(EscrowError::ExceedsEscrow);
+        }
+
+        data.amount -= amount;
+        storage::save_escrow(&env, id, &data);
+
+        accounting::release(&env, &data.token, amount);
+        let refund = fees::deduct_on_refund(&env, id, &data, amount);
+        let client = token::Client::new(&env, &data.token);
+        client.transfer(&env.current_contract_address(), &data.sender, &refund);
+
+        events::reduced(&env, id, amount, data.amount);
+        Ok(())
     }
 
-    pub fn get_received_ids(env: Env, user: Address) -> Vec<u64> {
-        env.storage()
-            .instance()
-            .get(&DataKey::UserReceived(user))
-            .unwrap_or(Vec::new(&env))
+    /// Moves the deadline later, giving recipients more time to claim. The
+    /// sender alone may do this.
+    pub fn extend_deadline(env: Env, id: u64, new_deadline: u64) -> Result<(), EscrowError> {
+        let data = storage::load_escrow(&env, id)?;
+
+        data.sender.require_auth();
+        Self::ensure_deadline_movable(&env, &data, new_deadline)?;
+        if new_deadline <= data.deadline {
+            return Err(EscrowError::InvalidDeadline);
+        }
+
+        Self::set_deadline(&env, id, data, new_deadline);
+        Ok(())
+    }
+
+    /// Moves the deadline earlier, cutting the claim window short. Needs every
+    /// recipient's authorization, and the sender's too when expiry releases
+    /// the funds, since it also cuts short the window to object.
+    pub fn shorten_deadline(env: Env, id: u64, new_deadline: u64) -> Result<(), EscrowError> {
+        let data = storage::load_escrow(&env, id)?;
+
+        for recipient in data.recipient_addresses(&env).iter() {
+            recipient.require_auth();
+        }
+        if data.on_expiry == ReleasePolicy::ReleaseOnExpiry {
+            data.sender.require_auth();
+        }
+        Self::ensure_deadline_movable(&env, &data, new_deadline)?;
+        if new_deadline >= data.deadline {
+            return Err(EscrowError::InvalidDeadline);
+        }
+
+        Self::set_deadline(&env, id, data, new_deadline);
+        Ok(())
     }
 
-    pub fn approve(env: Env, id: u64) {
-        let mut data: EscrowData = env
-            .storage()
-            .instance()
-            .get(&DataKey::Escrow(id))
-            .unwrap();
+    /// Creates a stream that vests linearly from `start` to `end`, split
+    /// between `recipients` by basis-point shares. Nothing vests before
+    /// `cliff`, with zero meaning no cliff. Streams need no approval;
+    /// acceptance is not supported.
+    pub fn create_vesting(
+        env: Env,
+        sender: Address,
+        recipients: Vec<(Address, u32)>,
+        amount: i128,
+        token: Address,
+        start: u64,
+        cliff: u64,
+        end: u64,
+        options: EscrowOptions,
+    ) -> Result<u64, EscrowError> {
+        sender.require_auth();
+        split::validate_bps(&recipients)?;
+        if options.require_acceptance {
+            return Err(EscrowError::InvalidState);
+        }
+        let vesting = vesting::build(&env, start, cliff, end, recipients.len())?;
+
+        let mut data = EscrowData::new(&env, sender, recipients, amount, token, 0);
+        data.set_vesting(vesting);
+        data.status = data.status.transition(EscrowStatus::Approved)?;
+        Self::open(&env, data.with_options(options))
+    }
+
+    /// Pays `recipient` everything vested since their last withdrawal.
+    pub fn withdraw_vested(env: Env, id: u64, recipient: Address) -> Result<(), EscrowError> {
+        recipient.require_auth();
+        pause::check_claims(&env)?;
+
+        let mut data = storage::load_escrow(&env, id)?;
+        let index = data.recipient_index(&recipient)?;
+        let amount = vesting::withdraw(&env, &mut data, index)?;
+        storage::save_escrow(&env, id, &data);
+
+        accounting::release(&env, &data.token, amount);
+        let payout = fees::deduct_on_claim(&env, id, &data, amount);
+        let client = token::Client::new(&env, &data.token);
+        client.transfer(&env.current_contract_address(), &recipient, &payout);
+
+        events::vested_withdrawn(&env, id, recipient.clone(), payout);
+        if data.status == EscrowStatus::Claimed {
+            events::claimed(&env, id, data.recipient_addresses(&env));
+        }
+        Ok(())
+    }
+
+    /// Stops a stream and returns the unvested remainder to the sender.
+    /// Recipients keep whatever vested up to now.
+    pub fn cancel_vesting(env: Env, id: u64) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
+
+        data.sender.require_auth();
+        pause::check_refunds(&env, false)?;
+        let unvested = vesting::stop(&env, &mut data)?;
+        storage::save_escrow(&env, id, &data);
+
+        accounting::release(&env, &data.token, unvested);
+        let refund = fees::deduct_on_refund(&env, id, &data, unvested);
+        if refund > 0 {
+            let client = token::Client::new(&env, &data.token);
+            client.transfer(&env.current_contract_address(), &data.sender, &refund);
+        }
+
+        events::vesting_stopped(&env, id, data.sender, unvested);
+        Ok(())
+    }
+
+    /// Releases the next milestone so recipients can claim its tranche.
+    pub fn approve_milestone(env: Env, id: u64, index: u32) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
 
         data.sender.require_auth();
-        assert_eq!(data.status, 0, "escrow not pending");
-        assert!(!data.approved, "already approved");
+        if !data.is_accepted() {
+            return Err(EscrowError::NotAccepted);
+        }
+        let milestone = milestones::release(&mut data, index)?;
+        milestones::settle(&mut data)?;
+        storage::save_escrow(&env, id, &data);
+
+        events::milestone_released(&env, id, index, milestone.amount);
+        Ok(())
+    }
 
-        data.approved = true;
-        data.status = 1;
-        env.storage().instance().set(&DataKey::Escrow(id), &data);
+    /// Returns an unreleased tranche to the sender once its deadline passed.
+    pub fn refund_milestone(env: Env, id: u64, index: u32) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
 
-        events::approved(&env, id, data.sender);
+        data.sender.require_auth();
+        let milestone = milestones::refund(&env, &mut data, index)?;
+        pause::check_refunds(&env, milestone.deadline > 0)?;
+        milestones::settle(&mut data)?;
+        storage::save_escrow(&env, id, &data);
+
+        accounting::release(&env, &data.token, milestone.amount);
+        let refund = fees::deduct_on_refund(&env, id, &data, milestone.amount);
+        let client = token::Client::new(&env, &data.token);
+        client.transfer(&env.current_contract_address(), &data.sender, &refund);
+
+        events::milestone_refunded(&env, id, index, milestone.amount);
+        Ok(())
     }
 
-    pub fn claim(env: Env, id: u64, caller: Address) {
+    /// Freezes claims and refunds until the arbiter resolves the escrow.
+    pub fn raise_dispute(env: Env, id: u64, caller: Address) -> Result<(), EscrowError> {
         caller.require_auth();
 
-        let mut data: EscrowData = env
-            .storage()
-            .instance()
-            .get(&DataKey::Escrow(id))
-            .unwrap();
+        let mut data = storage::load_escrow(&env, id)?;
+        if data.arbiter.is_none() {
+            return Err(EscrowError::NoArbiter);
+        }
+        if !data.is_participant(&caller) {
+            return Err(EscrowError::NotParticipant);
+        }
+        data.status.ensure_active()?;
 
-        assert!(
-            data.recipients.iter().any(|(r, _)| r == caller),
-            "not a recipient"
-        );
-        assert!(data.approved, "escrow not approved");
-        assert!(data.status == 1, "escrow not in claimable state");
+        data.status = data.status.transition(EscrowStatus::Disputed)?;
+        storage::save_escrow(&env, id, &data);
+
+        events::dispute_raised(&env, id, caller);
+        Ok(())
+    }
 
-        if data.deadline > 0 {
-            assert!(
-                env.ledger().timestamp() <= data.deadline,
-                "deadline passed"
-            );
+    /// Distributes the funds still locked in a disputed escrow. `payout_split`
+    /// holds basis-point shares for the sender and/or recipients. No protocol
+    /// fee is withheld from arbiter payouts.
+    pub fn resolve(
+        env: Env,
+        id: u64,
+        payout_split: Vec<(Address, u32)>,
+    ) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
+
+        let arbiter = data.arbiter.clone().ok_or(EscrowError::NoArbiter)?;
+        arbiter.require_auth();
+        pause::check_claims(&env)?;
+        if data.status != EscrowStatus::Disputed {
+            return Err(EscrowError::InvalidState);
+        }
+        split::validate_bps(&payout_split)?;
+        if !payout_split.iter().all(|(payee, _)| data.is_participant(&payee)) {
+            return Err(EscrowError::NotParticipant);
         }
 
-        data.status = 2;
-        env.storage().instance().set(&DataKey::Escrow(id), &data);
+        let locked = data.locked_amount(&env);
+        data.status = data.status.transition(EscrowStatus::Resolved)?;
+        storage::save_escrow(&env, id, &data);
+        accounting::release(&env, &data.token, locked);
 
         let client = token::Client::new(&env, &data.token);
         let contract = env.current_contract_address();
+        let payouts = split::split(&env, locked, &payout_split, BPS_DENOMINATOR);
+        for (payee, amount) in payouts.iter() {
+            if amount > 0 {
+                client.transfer(&contract, &payee, &amount);
+            }
+        }
+
+        events::dispute_resolved(&env, id, arbiter, payouts);
+        Ok(())
+    }
+
+    /// The user's first `MAX_PAGE_SIZE` created ids; page through the rest
+    /// with `get_created_page`.
+    pub fn get_created_ids(env: Env, user: Address) -> Vec<u64> {
+        storage::index_range(&env, UserIndex::Created, &user, 0, MAX_PAGE_SIZE)
+    }
+
+    /// The user's first `MAX_PAGE_SIZE` received ids; page through the rest
+    /// with `get_received_page`.
+    pub fn get_received_ids(env: Env, user: Address) -> Vec<u64> {
+        storage::index_range(&env, UserIndex::Received, &user, 0, MAX_PAGE_SIZE)
+    }
+
+    pub fn get_created_page(
+        env: Env,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+    ) -> Result<EscrowPage, EscrowError> {
+        Self::page(&env, UserIndex::Created, &user, cursor, limit)
+    }
+
+    pub fn get_received_page(
+        env: Env,
+        user: Address,
+        cursor: u32,
+        limit: u32,
+    ) -> Result<EscrowPage, EscrowError> {
+        Self::page(&env, UserIndex::Received, &user, cursor, limit)
+    }
+
+    /// Approves release on behalf of `approver`: the sender or the escrow's
+    /// third-party approver, or for escrows created with an approver set, one
+    /// of its members. Those escrows become `Approved` once `threshold`
+    /// members have approved.
+    pub fn approve(env: Env, id: u64, approver: Address) -> Result<(), EscrowError> {
+        approver.require_auth();
+
+        let mut data = storage::load_escrow(&env, id)?;
+        Self::ensure_approvable(&data)?;
+
+        if data.approvers.is_empty() {
+            if approver != data.approver.clone().unwrap_or(data.sender.clone()) {
+                return Err(EscrowError::NotApprover);
+            }
+        } else {
+            let count = approvals::record(&mut data, &approver)?;
+            events::approval_recorded(&env, id, approver.clone(), count);
+            if count < data.threshold {
+                storage::save_escrow(&env, id, &data);
+                return Ok(());
+            }
+        }
 
-        for (recipient, percentage) in data.recipients.iter() {
-            let share = data.amount * (percentage as i128) / 100;
-            client.transfer(&contract, &recipient, &share);
-            events::split_claimed(&env, id, recipient.clone(), share);
+        Self::mark_approved(&env, id, data, approver)
+    }
+
+    /// Approves the escrow once its oracle reports the condition fulfilled.
+    /// Callable by anyone.
+    pub fn approve_via_oracle(env: Env, id: u64) -> Result<(), EscrowError> {
+        let data = storage::load_escrow(&env, id)?;
+        Self::ensure_approvable(&data)?;
+
+        let oracle = data.oracle.clone().ok_or(EscrowError::InvalidState)?;
+        if data.status != EscrowStatus::Pending {
+            return Err(EscrowError::InvalidState);
         }
+        if !approvals::OracleClient::new(&env, &oracle).is_fulfilled(&id) {
+            return Err(EscrowError::NotFulfilled);
+        }
+
+        Self::mark_approved(&env, id, data, oracle)
+    }
 
-        let mut recipient_list = Vec::new(&env);
-        for (r, _) in data.recipients.iter() {
-            recipient_list.push_back(r.clone());
+    fn ensure_approvable(data: &EscrowData) -> Result<(), EscrowError> {
+        if !data.milestones.is_empty() || data.hashlock.is_some() {
+            return Err(EscrowError::InvalidState);
         }
+        if !data.is_accepted() {
+            return Err(EscrowError::NotAccepted);
+        }
+        Ok(())
+    }
+
+    fn mark_approved(
+        env: &Env,
+        id: u64,
+        mut data: EscrowData,
+        approver: Address,
+    ) -> Result<(), EscrowError> {
+        data.status = data.status.transition(EscrowStatus::Approved)?;
+        storage::save_escrow(env, id, &data);
+
+        events::approved(env, id, approver);
+        Ok(())
+    }
 
-        events::claimed(&env, id, recipient_list);
+    /// Withdraws an approval recorded by `approve` while the threshold is
+    /// still unmet.
+    pub fn revoke_approval(env: Env, id: u64, approver: Address) -> Result<(), EscrowError> {
+        approver.require_auth();
+
+        let mut data = storage::load_escrow(&env, id)?;
+        let count = approvals::revoke(&mut data, &approver)?;
+        storage::save_escrow(&env, id, &data);
+
+        events::approval_revoked(&env, id, approver, count);
+        Ok(())
     }
 
-    pub fn refund(env: Env, id: u64) {
-        let mut data: EscrowData = env
-            .storage()
-            .instance()
-            .get(&DataKey::Escrow(id))
-            .unwrap();
+    /// Withdraws `caller`'s own share. The escrow becomes `Claimed` once every
+    /// recipient has withdrawn. For milestone escrows this pulls the caller's
+    /// share of every released tranche, regardless of deadlines.
+    pub fn claim(env: Env, id: u64, caller: Address) -> Result<(), EscrowError> {
+        caller.require_auth();
+        pause::check_claims(&env)?;
+
+        let mut data = storage::load_escrow(&env, id)?;
+
+        let index = data.recipient_index(&caller)?;
+        if !data.milestones.is_empty() {
+            return Self::claim_milestones(&env, id, data, caller, index);
+        }
+        if data.vesting().is_some() {
+            return Err(EscrowError::InvalidState);
+        }
+        if data.status == EscrowStatus::Disputed {
+            return Err(EscrowError::Disputed);
+        }
+        let released = expiry::is_released(&env, &data);
+        if data.status == EscrowStatus::Pending {
+            if !released {
+                return Err(EscrowError::NotApproved);
+            }
+            data.status = data.status.transition(EscrowStatus::Approved)?;
+        }
+        if !released && data.deadline > 0 && env.ledger().timestamp() > data.deadline {
+            return Err(EscrowError::DeadlinePassed);
+        }
+        if data.claimed.get_unchecked(index) {
+            return Err(EscrowError::AlreadyClaimed);
+        }
+
+        data.claimed.set(index, true);
+        let next = if data.claimed.iter().all(|c| c) {
+            EscrowStatus::Claimed
+        } else {
+            EscrowStatus::PartiallyClaimed
+        };
+        if next != data.status {
+            data.status = data.status.transition(next)?;
+        }
+        storage::save_escrow(&env, id, &data);
+
+        let (_, share) = data.shares(&env).get_unchecked(index);
+        accounting::release(&env, &data.token, share);
+        let payout = fees::deduct_on_claim(&env, id, &data, share);
+        let client = token::Client::new(&env, &data.token);
+        client.transfer(&env.current_contract_address(), &caller, &payout);
+        events::split_claimed(&env, id, caller, payout);
+
+        if data.status == EscrowStatus::Claimed {
+            events::claimed(&env, id, data.recipient_addresses(&env));
+        }
+        Ok(())
+    }
+
+    /// Returns every unclaimed share to the sender once the deadline has passed.
+    pub fn refund(env: Env, id: u64) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
 
         data.sender.require_auth();
-        assert!(data.status == 0 || data.status == 1, "cannot refund");
-        assert!(
-            data.deadline == 0 || env.ledger().timestamp() > data.deadline,
-            "deadline not passed"
-        );
+        if !data.is_single_release() {
+            return Err(EscrowError::InvalidState);
+        }
+        if data.status == EscrowStatus::Disputed {
+            return Err(EscrowError::Disputed);
+        }
+        data.status = data.status.transition(EscrowStatus::Refunded)?;
+        if data.deadline > 0 && env.ledger().timestamp() <= data.deadline {
+            return Err(EscrowError::DeadlineNotPassed);
+        }
+        if expiry::is_released(&env, &data) {
+            return Err(EscrowError::ReleasedOnExpiry);
+        }
+        pause::check_refunds(&env, data.deadline > 0)?;
 
-        data.status = 3;
-        env.storage().instance().set(&DataKey::Escrow(id), &data);
+        storage::save_escrow(&env, id, &data);
 
         let client = token::Client::new(&env, &data.token);
         let contract = env.current_contract_address();
-        client.transfer(&contract, &data.sender, &data.amount);
+        let unclaimed = data.unclaimed_amount(&env);
+        accounting::release(&env, &data.token, unclaimed);
+        let refund = fees::deduct_on_refund(&env, id, &data, unclaimed);
+        client.transfer(&contract, &data.sender, &refund);
 
         events::refunded(&env, id, data.sender);
+        Ok(())
+    }
+
+    /// Blocks release on expiry: once the sender objects before the deadline,
+    /// the escrow refunds on expiry unless approved.
+    pub fn object(env: Env, id: u64) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
+
+        data.sender.require_auth();
+        expiry::object(&env, &mut data)?;
+        storage::save_escrow(&env, id, &data);
+
+        events::objected(&env, id, data.sender);
+        Ok(())
+    }
+
+    /// Calls the deal off by mutual consent: with the sender and every
+    /// recipient authorizing, all funds still held go back to the sender
+    /// regardless of the deadline.
+    pub fn cancel(env: Env, id: u64) -> Result<(), EscrowError> {
+        let mut data = storage::load_escrow(&env, id)?;
+
+        data.sender.require_auth();
+        for recipient in data.recipient_addresses(&env).iter() {
+            recipient.require_auth();
+        }
+        data.status.ensure_active()?;
+        let expired = data.deadline > 0 && env.ledger().timestamp() > data.deadline;
+        pause::check_refunds(&env, expired)?;
+
+        let locked = data.locked_amount(&env);
+        data.status = data.status.transition(EscrowStatus::Cancelled)?;
+        storage::save_escrow(&env, id, &data);
+
+        accounting::release(&env, &data.token, locked);
+        let refund = fees::deduct_on_refund(&env, id, &data, locked);
+        if refund > 0 {
+            let client = token::Client::new(&env, &data.token);
+            client.transfer(&env.current_contract_address(), &data.sender, &refund);
+        }
+
+        events::cancelled(&env, id, data.sender, locked);
+        Ok(())
+    }
+
+    /// Records `recipient`'s consent to an escrow created with
+    /// `require_acceptance`.
+    pub fn accept(env: Env, id: u64, recipient: Address) -> Result<(), EscrowError> {
+        recipient.require_auth();
+
+        let mut data = storage::load_escrow(&env, id)?;
+        let index = data.recipient_index(&recipient)?;
+        if data.accepted.is_empty()
+            || data.accepted.get_unchecked(index)
+            || data.status != EscrowStatus::Pending
+        {
+            return Err(EscrowError::InvalidState);
+        }
+
+        data.accepted.set(index, true);
+        storage::save_escrow(&env, id, &data);
+
+        events::accepted(&env, id, recipient);
+        Ok(())
+    }
+
+    /// Declines an escrow awaiting `recipient`'s acceptance: the sender is
+    /// refunded at once and the id leaves the recipient's received index.
+    pub fn reject(env: Env, id: u64, recipient: Address) -> Result<(), EscrowError> {
+        recipient.require_auth();
+
+        let mut data = storage::load_escrow(&env, id)?;
+        let index = data.recipient_index(&recipient)?;
+        if data.accepted.is_empty()
+            || data.accepted.get_unchecked(index)
+            || data.status != EscrowStatus::Pending
+        {
+            return Err(EscrowError::InvalidState);
+        }
+        pause::check_refunds(&env, false)?;
+
+        let locked = data.locked_amount(&env);
+        data.status = data.status.transition(EscrowStatus::Refunded)?;
+        storage::save_escrow(&env, id, &data);
+        storage::index_remove(&env, UserIndex::Received, &recipient, id);
+
+        accounting::release(&env, &data.token, locked);
+        let refund = fees::deduct_on_refund(&env, id, &data, locked);
+        let client = token::Client::new(&env, &data.token);
+        client.transfer(&env.current_contract_address(), &data.sender, &refund);
+
+        events::rejected(&env, id, recipient, locked);
+        Ok(())
+    }
+
+    /// Hands `recipient`'s share back to the sender. The remaining shares are
+    /// re-normalized; when the last recipient waives, the escrow is refunded.
+    pub fn waive(env: Env, id: u64, recipient: Address) -> Result<(), EscrowError> {
+        recipient.require_auth();
+
+        let mut data = storage::load_escrow(&env, id)?;
+        let index = data.recipient_index(&recipient)?;
+        Self::ensure_adjustable(&data)?;
+        pause::check_refunds(&env, false)?;
+
+        let (_, share) = data.shares(&env).get_unchecked(index);
+        if data.recipients.len() == 1 {
+            data.status = data.status.transition(EscrowStatus::Refunded)?;
+        } else {
+            let (_, bps) = data.recipients.get_unchecked(index);
+            data.recipients.remove(index);
+            data.claimed.remove(index);
+            if !data.accepted.is_empty() {
+                data.accepted.remove(index);
+            }
+            data.recipients = split::renormalize(&env, &data.recipients, BPS_DENOMINATOR - bps);
+            data.amount -= share;
+        }
+        storage::save_escrow(&env, id, &data);
+
+        accounting::release(&env, &data.token, share);
+        let refund = fees::deduct_on_refund(&env, id, &data, share);
+        let client = token::Client::new(&env, &data.token);
+        client.transfer(&env.current_contract_address(), &data.sender, &refund);
+
+        events::waived(&env, id, recipient, share);
+        if data.status == EscrowStatus::Refunded {
+            events::refunded(&env, id, data.sender);
+        }
+        Ok(())
+    }
+
+    pub fn get_escrow(env: Env, id: u64) -> Result<EscrowData, EscrowError> {
+        storage::load_escrow(&env, id)
+    }
+
+    /// Extends the TTL of an escrow record. Callable by anyone willing to pay the fee.
+    pub fn bump(env: Env, id: u64) -> Result<(), EscrowError> {
+        storage::bump_escrow(&env, id)
+    }
+
+    fn open(env: &Env, mut data: EscrowData) -> Result<u64, EscrowError> {
+        pause::check_creation(env)?;
+        if data.amount <= 0 {
+            return Err(EscrowError::NonPositiveAmount);
+        }
+        tokens::check(env, &data.token, data.amount)?;
+        // A party to the escrow could dispute and then rule in its own favour.
+        if data.arbiter.as_ref().is_some_and(|arbiter| data.is_participant(arbiter)) {
+            return Err(EscrowError::InvalidArbiter);
+        }
+        approvals::validate(&data)?;
+        expiry::validate(&data)?;
+        let now = env.ledger().timestamp();
+        if (data.deadline > 0 && data.deadline <= now)
+            || data.milestones.iter().any(|m| m.deadline > 0 && m.deadline <= now)
+        {
+            return Err(EscrowError::InvalidDeadline);
+        }
+
+        let received = accounting::deposit(env, &data.token, &data.sender, data.amount)?;
+        if received != data.amount {
+            // Tranche amounts are fixed up front, so they cannot absorb a shortfall.
+            if !data.milestones.is_empty() {
+                return Err(EscrowError::DepositShortfall);
+            }
+            data.amount = received;
+        }
+        vesting::check_shares(env, &data)?;
+        fees::snapshot(env, &mut data);
+
+        let id = storage::next_id(env);
+
+        storage::save_escrow(env, id, &data);
+
+        storage::index_push(env, UserIndex::Created, &data.sender, id);
+        for (recipient, _) in data.recipients.iter() {
+            storage::index_push(env, UserIndex::Received, &recipient, id);
+        }
+
+        events::created(env, id, data.sender.clone(), data.amount, data.deadline);
+
+        Ok(id)
+    }
+
+    /// Open-ended escrows keep no deadline, and a new one must lie in the future.
+    fn ensure_deadline_movable(
+        env: &Env,
+        data: &EscrowData,
+        new_deadline: u64,
+    ) -> Result<(), EscrowError> {
+        data.status.ensure_active()?;
+        if data.deadline == 0 {
+            return Err(EscrowError::InvalidState);
+        }
+        if env.ledger().timestamp() > data.deadline {
+            return Err(EscrowError::DeadlinePassed);
+        }
+        if new_deadline <= env.ledger().timestamp() {
+            return Err(EscrowError::InvalidDeadline);
+        }
+        Ok(())
+    }
+
+    fn set_deadline(env: &Env, id: u64, mut data: EscrowData, deadline: u64) {
+        let previous = data.deadline;
+        data.deadline = deadline;
+        storage::save_escrow(env, id, &data);
+
+        events::deadline_changed(env, id, previous, deadline);
+    }
+
+    /// Only single-release escrows with every share still unclaimed may
+    /// change amount.
+    fn ensure_adjustable(data: &EscrowData) -> Result<(), EscrowError> {
+        if !data.is_single_release() {
+            return Err(EscrowError::InvalidState);
+        }
+        match data.status {
+            EscrowStatus::Pending | EscrowStatus::Approved => Ok(()),
+            EscrowStatus::Disputed => Err(EscrowError::Disputed),
+            _ => Err(EscrowError::InvalidState),
+        }
     }
 
-    pub fn get_escrow(env: Env, id: u64) -> EscrowData {
-        env.storage()
-            .instance()
-            .get(&DataKey::Escrow(id))
-            .unwrap()
+    fn claim_milestones(
+        env: &Env,
+        id: u64,
+        mut data: EscrowData,
+        caller: Address,
+        index: u32,
+    ) -> Result<(), EscrowError> {
+        let amount = milestones::claim(env, id, &mut data, &caller, index)?;
+        milestones::settle(&mut data)?;
+        storage::save_escrow(env, id, &data);
+
+        accounting::release(env, &data.token, amount);
+        let payout = fees::deduct_on_claim(env, id, &data, amount);
+        let client = token::Client::new(env, &data.token);
+        client.transfer(&env.current_contract_address(), &caller, &payout);
+
+        if data.status == EscrowStatus::Claimed {
+            events::claimed(env, id, data.recipient_addresses(env));
+        }
+        Ok(())
     }
 
-    fn next_id(env: &Env) -> u64 {
-        let key = DataKey::NextId;
-        let mut id: u64 = env.storage().instance().get(&key).unwrap_or(0);
-        id += 1;
-        env.storage().instance().set(&key, &id);
-        id
+    fn page(
+        env: &Env,
+        index: UserIndex,
+        user: &Address,
+        cursor: u32,
+        limit: u32,
+    ) -> Result<EscrowPage, EscrowError> {
+        if limit == 0 || limit > MAX_PAGE_SIZE {
+            return Err(EscrowError::InvalidPageSize);
+        }
+
+        let ids = storage::index_range(env, index, user, cursor, limit);
+        let mut entries = Vec::new(env);
+        for id in ids.iter() {
+            entries.push_back((id, storage::load_escrow(env, id)?));
+        }
+
+        let next = cursor + ids.len();
+        let next_cursor = if next < storage::index_len(env, index, user) {
+            Some(next)
+        } else {
+            None
+        };
+
+        Ok(EscrowPage { entries, next_cursor })
     }
 }
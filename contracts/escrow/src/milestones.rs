@@ -0,0 +1,192 @@
+use soroban_sdk::{contracttype, Address, Env, Vec};
+
+use crate::{events, flags, split, EscrowData, EscrowError, EscrowStatus, BPS_DENOMINATOR};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum MilestoneStatus {
+    Pending,
+    Released,
+    /// Every recipient has withdrawn their share of the tranche.
+    Claimed,
+    Refunded,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Milestone {
+    pub amount:   i128,
+    /// Zero means the tranche can be refunded at any time while unreleased.
+    pub deadline: u64,
+    pub status:   MilestoneStatus,
+    /// Parallel to the escrow's `recipients`.
+    pub claimed:  Vec<bool>,
+}
+
+/// Builds the milestone list from `(amount, deadline)` pairs and returns it
+/// together with the escrow total.
+pub(crate) fn build(
+    env: &Env,
+    specs: &Vec<(i128, u64)>,
+    recipient_count: u32,
+) -> Result<(Vec<Milestone>, i128), EscrowError> {
+    if specs.is_empty() {
+        return Err(EscrowError::NoMilestones);
+    }
+
+    let mut milestones = Vec::new(env);
+    let mut total: i128 = 0;
+    for (amount, deadline) in specs.iter() {
+        if amount <= 0 {
+            return Err(EscrowError::NonPositiveAmount);
+        }
+        total = total.checked_add(amount).ok_or(EscrowError::Overflow)?;
+        milestones.push_back(Milestone {
+            amount,
+            deadline,
+            status: MilestoneStatus::Pending,
+            claimed: flags(env, recipient_count, false),
+        });
+    }
+    Ok((milestones, total))
+}
+
+/// Releases milestone `index`. Milestones are released strictly in order;
+/// refunded ones are skipped.
+pub(crate) fn release(data: &mut EscrowData, index: u32) -> Result<Milestone, EscrowError> {
+    data.status.ensure_active()?;
+    let mut milestone = data
+        .milestones
+        .get(index)
+        .ok_or(EscrowError::MilestoneNotFound)?;
+    if milestone.status != MilestoneStatus::Pending {
+        return Err(EscrowError::InvalidState);
+    }
+    if data
+        .milestones
+        .iter()
+        .take(index as usize)
+        .any(|m| m.status == MilestoneStatus::Pending)
+    {
+        return Err(EscrowError::MilestoneOutOfOrder);
+    }
+
+    milestone.status = MilestoneStatus::Released;
+    data.milestones.set(index, milestone.clone());
+    Ok(milestone)
+}
+
+/// Returns unreleased milestone `index` to the sender once its deadline passed.
+pub(crate) fn refund(
+    env: &Env,
+    data: &mut EscrowData,
+    index: u32,
+) -> Result<Milestone, EscrowError> {
+    data.status.ensure_active()?;
+    let mut milestone = data
+        .milestones
+        .get(index)
+        .ok_or(EscrowError::MilestoneNotFound)?;
+    if milestone.status != MilestoneStatus::Pending {
+        return Err(EscrowError::InvalidState);
+    }
+    if milestone.deadline > 0 && env.ledger().timestamp() <= milestone.deadline {
+        return Err(EscrowError::DeadlineNotPassed);
+    }
+
+    milestone.status = MilestoneStatus::Refunded;
+    data.milestones.set(index, milestone.clone());
+    Ok(milestone)
+}
+
+/// Marks every released tranche owed to recipient `index` as withdrawn and
+/// returns the total to transfer.
+pub(crate) fn claim(
+    env: &Env,
+    id: u64,
+    data: &mut EscrowData,
+    recipient: &Address,
+    index: u32,
+) -> Result<i128, EscrowError> {
+    data.status.ensure_active()?;
+    let mut total: i128 = 0;
+    for (position, mut milestone) in data.milestones.clone().iter().enumerate() {
+        if milestone.status != MilestoneStatus::Released
+            || milestone.claimed.get_unchecked(index)
+        {
+            continue;
+        }
+
+        let (_, share) = split::split(env, milestone.amount, &data.recipients, BPS_DENOMINATOR)
+            .get_unchecked(index);
+        milestone.claimed.set(index, true);
+        if milestone.claimed.iter().all(|c| c) {
+            milestone.status = MilestoneStatus::Claimed;
+        }
+        data.milestones.set(position as u32, milestone);
+
+        events::milestone_claimed(env, id, position as u32, recipient.clone(), share);
+        total += share;
+    }
+
+    if total == 0 {
+        return Err(EscrowError::NothingToClaim);
+    }
+    Ok(total)
+}
+
+/// Unreleased tranches plus released shares not yet withdrawn.
+pub(crate) fn locked_amount(env: &Env, data: &EscrowData) -> i128 {
+    let mut total: i128 = 0;
+    for milestone in data.milestones.iter() {
+        match milestone.status {
+            MilestoneStatus::Pending => total += milestone.amount,
+            MilestoneStatus::Released => {
+                let shares = split::split(env, milestone.amount, &data.recipients, BPS_DENOMINATOR);
+                for (i, (_, share)) in shares.iter().enumerate() {
+                    if !milestone.claimed.get_unchecked(i as u32) {
+                        total += share;
+                    }
+                }
+            }
+            MilestoneStatus::Claimed | MilestoneStatus::Refunded => {}
+        }
+    }
+    total
+}
+
+/// Escrow-level status implied by the milestones' states.
+pub(crate) fn derived_status(data: &EscrowData) -> EscrowStatus {
+    let mut any_pending = false;
+    let mut any_released = false;
+    let mut any_claimed = false;
+
+    for milestone in data.milestones.iter() {
+        match milestone.status {
+            MilestoneStatus::Pending => any_pending = true,
+            MilestoneStatus::Released => {
+                any_released = true;
+                any_claimed |= milestone.claimed.iter().any(|c| c);
+            }
+            MilestoneStatus::Claimed => any_claimed = true,
+            MilestoneStatus::Refunded => {}
+        }
+    }
+
+    match (any_pending || any_released, any_claimed, any_released) {
+        (false, false, _) => EscrowStatus::Refunded,
+        (false, true, _) => EscrowStatus::Claimed,
+        (true, true, _) => EscrowStatus::PartiallyClaimed,
+        (true, false, true) => EscrowStatus::Approved,
+        (true, false, false) => EscrowStatus::Pending,
+    }
+}
+
+/// Moves the escrow to the status implied by its milestones.
+pub(crate) fn settle(data: &mut EscrowData) -> Result<(), EscrowError> {
+    let next = derived_status(data);
+    if next != data.status {
+        data.status = data.status.transition(next)?;
+    }
+    Ok(())
+}
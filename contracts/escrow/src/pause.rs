@@ -0,0 +1,50 @@
+use soroban_sdk::{contracttype, Env};
+
+use crate::{admin, DataKey, EscrowError};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct PauseFlags {
+    pub creation:               bool,
+    pub claims:                 bool,
+    pub refunds:                bool,
+    /// Keep refunds of escrows past their deadline available while `refunds` is set.
+    pub expired_refunds_exempt: bool,
+}
+
+pub(crate) fn get_flags(env: &Env) -> PauseFlags {
+    env.storage()
+        .instance()
+        .get(&DataKey::Pause)
+        .unwrap_or_default()
+}
+
+pub(crate) fn set_flags(env: &Env, flags: &PauseFlags) {
+    env.storage().instance().set(&DataKey::Pause, flags);
+}
+
+/// Also refuses deposits until the contract has an admin, so funds never reach
+/// a deployment still waiting for `initialize`.
+pub(crate) fn check_creation(env: &Env) -> Result<(), EscrowError> {
+    admin::get_admin(env)?;
+    if get_flags(env).creation {
+        return Err(EscrowError::Paused);
+    }
+    Ok(())
+}
+
+pub(crate) fn check_claims(env: &Env) -> Result<(), EscrowError> {
+    if get_flags(env).claims {
+        return Err(EscrowError::Paused);
+    }
+    Ok(())
+}
+
+/// `expired` is whether the deadline guarding the refunded funds has passed.
+pub(crate) fn check_refunds(env: &Env, expired: bool) -> Result<(), EscrowError> {
+    let flags = get_flags(env);
+    if flags.refunds && !(expired && flags.expired_refunds_exempt) {
+        return Err(EscrowError::Paused);
+    }
+    Ok(())
+}
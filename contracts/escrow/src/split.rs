@@ -0,0 +1,139 @@
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{EscrowError, BPS_DENOMINATOR};
+
+/// Converts the original whole-percentage shares (summing to 100) to basis points,
+/// applying the same checks as `validate_bps`.
+pub(crate) fn percentages_to_bps(
+    env: &Env,
+    recipients: &Vec<(Address, u32)>,
+) -> Result<Vec<(Address, u32)>, EscrowError> {
+    let converted = scale_percentages(env, recipients)?;
+    validate_bps(&converted)?;
+    Ok(converted)
+}
+
+/// Lenient `percentages_to_bps` for records stored before shares were
+/// validated: duplicate recipients are merged and zero shares dropped.
+pub(crate) fn legacy_percentages_to_bps(
+    env: &Env,
+    recipients: &Vec<(Address, u32)>,
+) -> Result<Vec<(Address, u32)>, EscrowError> {
+    let mut merged: Vec<(Address, u32)> = Vec::new(env);
+    for (recipient, percentage) in recipients.iter() {
+        if percentage == 0 {
+            continue;
+        }
+        match merged.iter().position(|(other, _)| other == recipient) {
+            Some(i) => {
+                let total = merged
+                    .get_unchecked(i as u32)
+                    .1
+                    .checked_add(percentage)
+                    .ok_or(EscrowError::BadPercentages)?;
+                merged.set(i as u32, (recipient, total));
+            }
+            None => merged.push_back((recipient, percentage)),
+        }
+    }
+    scale_percentages(env, &merged)
+}
+
+fn scale_percentages(
+    env: &Env,
+    recipients: &Vec<(Address, u32)>,
+) -> Result<Vec<(Address, u32)>, EscrowError> {
+    if recipients.is_empty() {
+        return Err(EscrowError::NoRecipients);
+    }
+
+    let mut total: u32 = 0;
+    let mut converted = Vec::new(env);
+    for (recipient, percentage) in recipients.iter() {
+        total = total
+            .checked_add(percentage)
+            .ok_or(EscrowError::BadPercentages)?;
+        if total > 100 {
+            return Err(EscrowError::BadPercentages);
+        }
+        converted.push_back((recipient, percentage * (BPS_DENOMINATOR / 100)));
+    }
+    if total != 100 {
+        return Err(EscrowError::BadPercentages);
+    }
+    Ok(converted)
+}
+
+/// Checks basis-point shares: non-empty, no duplicates, no zero shares, and
+/// summing to exactly `BPS_DENOMINATOR`.
+pub(crate) fn validate_bps(recipients: &Vec<(Address, u32)>) -> Result<(), EscrowError> {
+    if recipients.is_empty() {
+        return Err(EscrowError::NoRecipients);
+    }
+
+    let mut total: u32 = 0;
+    for (i, (recipient, bps)) in recipients.iter().enumerate() {
+        if bps == 0 {
+            return Err(EscrowError::ZeroShare);
+        }
+        if recipients
+            .iter()
+            .skip(i + 1)
+            .any(|(other, _)| other == recipient)
+        {
+            return Err(EscrowError::DuplicateRecipient);
+        }
+        total = total.checked_add(bps).ok_or(EscrowError::BadShares)?;
+    }
+    if total != BPS_DENOMINATOR {
+        return Err(EscrowError::BadShares);
+    }
+    Ok(())
+}
+
+/// `amount * num / den`, rounded down, without the overflow risk of
+/// multiplying first. Expects a non-negative `amount` and `num <= den`.
+pub(crate) fn mul_div(amount: i128, num: u64, den: u64) -> i128 {
+    let quotient = amount / den as i128;
+    let remainder = amount % den as i128;
+    quotient * num as i128 + (remainder as u128 * num as u128 / den as u128) as i128
+}
+
+/// Splits `amount` between `recipients` in proportion to their weights out of
+/// `total_weight`. Rounding dust goes to the last recipient, so the shares
+/// always sum to exactly `amount`.
+pub(crate) fn split(
+    env: &Env,
+    amount: i128,
+    recipients: &Vec<(Address, u32)>,
+    total_weight: u32,
+) -> Vec<(Address, i128)> {
+    let mut shares = Vec::new(env);
+    let mut distributed: i128 = 0;
+    let last = recipients.len() - 1;
+
+    for (i, (recipient, weight)) in recipients.iter().enumerate() {
+        let share = if i as u32 == last {
+            amount - distributed
+        } else {
+            mul_div(amount, weight as u64, total_weight as u64)
+        };
+        distributed += share;
+        shares.push_back((recipient, share));
+    }
+    shares
+}
+
+/// Rescales basis-point shares whose weights sum to `total_weight` so they sum
+/// to `BPS_DENOMINATOR` again, keeping their proportions.
+pub(crate) fn renormalize(
+    env: &Env,
+    recipients: &Vec<(Address, u32)>,
+    total_weight: u32,
+) -> Vec<(Address, u32)> {
+    let mut rescaled = Vec::new(env);
+    for (recipient, bps) in split(env, BPS_DENOMINATOR as i128, recipients, total_weight).iter() {
+        rescaled.push_back((recipient, bps as u32));
+    }
+    rescaled
+}
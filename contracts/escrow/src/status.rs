@@ -0,0 +1,68 @@
+use soroban_sdk::contracttype;
+
+use crate::EscrowError;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum EscrowStatus {
+    Pending,
+    Approved,
+    Claimed,
+    Refunded,
+    /// Some, but not all, recipients have withdrawn their share.
+    PartiallyClaimed,
+    /// Frozen until the arbiter resolves it.
+    Disputed,
+    /// Funds distributed by the arbiter.
+    Resolved,
+    /// Called off by the sender and every recipient; remaining funds refunded.
+    Cancelled,
+}
+
+impl EscrowStatus {
+    /// Validates a lifecycle edge and returns the new status.
+    pub fn transition(self, to: EscrowStatus) -> Result<EscrowStatus, EscrowError> {
+        use EscrowStatus::*;
+
+        match (self, to) {
+            (Pending, Approved)
+            | (Pending, Refunded)
+            | (Approved, Claimed)
+            | (Approved, PartiallyClaimed)
+            | (Approved, Refunded)
+            | (PartiallyClaimed, Claimed)
+            | (PartiallyClaimed, Refunded)
+            | (Pending, Disputed)
+            | (Approved, Disputed)
+            | (PartiallyClaimed, Disputed)
+            | (Disputed, Resolved)
+            | (Pending, Cancelled)
+            | (Approved, Cancelled)
+            | (PartiallyClaimed, Cancelled) => Ok(to),
+            _ => Err(EscrowError::InvalidState),
+        }
+    }
+
+    /// Rejects disputed and finished escrows.
+    pub fn ensure_active(self) -> Result<(), EscrowError> {
+        match self {
+            EscrowStatus::Disputed => Err(EscrowError::Disputed),
+            EscrowStatus::Claimed
+            | EscrowStatus::Refunded
+            | EscrowStatus::Resolved
+            | EscrowStatus::Cancelled => Err(EscrowError::InvalidState),
+            _ => Ok(()),
+        }
+    }
+
+    /// Maps the `u32` codes stored by the first contract release.
+    pub fn from_legacy(code: u32) -> Result<EscrowStatus, EscrowError> {
+        match code {
+            0 => Ok(EscrowStatus::Pending),
+            1 => Ok(EscrowStatus::Approved),
+            2 => Ok(EscrowStatus::Claimed),
+            3 => Ok(EscrowStatus::Refunded),
+            _ => Err(EscrowError::InvalidState),
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use soroban_sdk::{symbol_short, Address, Env, IntoVal, Map, Symbol, TryFromVal, Val, Vec};
+
+use crate::{accounting, upgrade, DataKey, EscrowData, EscrowError, LegacyEscrowData};
+
+pub(crate) const DAY_IN_LEDGERS: u32 = 17_280;
+
+pub(crate) const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
+pub(crate) const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+pub(crate) const ESCROW_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+pub(crate) const ESCROW_LIFETIME_THRESHOLD: u32 = ESCROW_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+/// Legacy index entries rewritten per call, so large indexes migrate over
+/// several writes rather than one unbounded one.
+pub const INDEX_MIGRATION_CHUNK: u32 = 20;
+
+pub(crate) fn get_persistent<V: TryFromVal<Env, Val>>(env: &Env, key: &DataKey) -> Option<V> {
+    let value = env.storage().persistent().get(key);
+    if value.is_some() {
+        extend_persistent(env, key);
+    }
+    value
+}
+
+pub(crate) fn set_persistent<V: IntoVal<Env, Val>>(env: &Env, key: &DataKey, value: &V) {
+    env.storage().persistent().set(key, value);
+    extend_persistent(env, key);
+}
+
+pub(crate) fn extend_instance(env: &Env) {
+    env.storage()
+        .instance()
+        .extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
+}
+
+fn extend_persistent(env: &Env, key: &DataKey) {
+    env.storage()
+        .persistent()
+        .extend_ttl(key, ESCROW_LIFETIME_THRESHOLD, ESCROW_BUMP_AMOUNT);
+}
+
+/// Reads an escrow record and extends its TTL.
+///
+/// Records written by earlier releases live in instance storage and may still
+/// use the `u32`-status layout. On first read they are upgraded, moved to
+/// persistent storage and, since they predate per-token accounting, counted
+/// as locked.
+pub(crate) fn load_escrow(env: &Env, id: u64) -> Result<EscrowData, EscrowError> {
+    extend_instance(env);
+
+    let key = DataKey::Escrow(id);
+    if let Some(raw) = env.storage().persistent().get(&key) {
+        extend_persistent(env, &key);
+        return decode_escrow(env, raw);
+    }
+
+    let raw = env
+        .storage()
+        .instance()
+        .get(&key)
+        .ok_or(EscrowError::NotFound)?;
+    let data = decode_escrow(env, raw)?;
+    save_escrow(env, id, &data);
+    accounting::adopt(env, &data.token, data.locked_amount(env));
+    Ok(data)
+}
+
+fn decode_escrow(env: &Env, raw: Map<Symbol, Val>) -> Result<EscrowData, EscrowError> {
+    if raw.contains_key(symbol_short!("approved")) {
+        LegacyEscrowData::try_from_val(env, &raw.to_val())
+            .map_err(|_| EscrowError::InvalidState)?
+            .migrate(env)
+    } else {
+        EscrowData::try_from_val(env, &raw.to_val()).map_err(|_| EscrowError::InvalidState)
+    }
+}
+
+pub(crate) fn save_escrow(env: &Env, id: u64, data: &EscrowData) {
+    let key = DataKey::Escrow(id);
+    env.storage().persistent().set(&key, data);
+    extend_persistent(env, &key);
+
+    if env.storage().instance().has(&key) {
+        env.storage().instance().remove(&key);
+    }
+}
+
+pub(crate) fn bump_escrow(env: &Env, id: u64) -> Result<(), EscrowError> {
+    extend_instance(env);
+
+    let key = DataKey::Escrow(id);
+    if !env.storage().persistent().has(&key) {
+        // Legacy instance records ride on the instance TTL extended above.
+        return if env.storage().instance().has(&key) {
+            Ok(())
+        } else {
+            Err(EscrowError::NotFound)
+        };
+    }
+    extend_persistent(env, &key);
+    Ok(())
+}
+
+pub(crate) fn next_id(env: &Env) -> u64 {
+    extend_instance(env);
+
+    let key = DataKey::NextId;
+    let mut id: u64 = env.storage().instance().get(&key).unwrap_or(0);
+    if id == 0 {
+        // Fresh deployment: record the layout before `NextId` marks it as
+        // holding escrows.
+        upgrade::set_schema_version(env, upgrade::SCHEMA_VERSION);
+    }
+    id += 1;
+    env.storage().instance().set(&key, &id);
+    id
+}
+
+/// Per-user escrow index. Each user has a counter plus one entry per id, so
+/// reads and writes touch a bounded number of ledger entries.
+#[derive(Clone, Copy)]
+pub(crate) enum UserIndex {
+    Created,
+    Received,
+}
+
+impl UserIndex {
+    fn count_key(self, user: &Address) -> DataKey {
+        match self {
+            UserIndex::Created => DataKey::UserCreatedCount(user.clone()),
+            UserIndex::Received => DataKey::UserReceivedCount(user.clone()),
+        }
+    }
+
+    fn entry_key(self, user: &Address, position: u32) -> DataKey {
+        match self {
+            UserIndex::Created => DataKey::UserCreatedEntry(user.clone(), position),
+            UserIndex::Received => DataKey::UserReceivedEntry(user.clone(), position),
+        }
+    }
+
+    fn legacy_key(self, user: &Address) -> DataKey {
+        match self {
+            UserIndex::Created => DataKey::UserCreated(user.clone()),
+            UserIndex::Received => DataKey::UserReceived(user.clone()),
+        }
+    }
+
+    fn legacy_cursor_key(self, user: &Address) -> DataKey {
+        match self {
+            UserIndex::Created => DataKey::UserCreatedCursor(user.clone()),
+            UserIndex::Received => DataKey::UserReceivedCursor(user.clone()),
+        }
+    }
+}
+
+/// Reads the single-vector index written by earlier releases, if any.
+fn legacy_ids(env: &Env, key: &DataKey) -> Option<Vec<u64>> {
+    env.storage()
+        .persistent()
+        .get(key)
+        .or_else(|| env.storage().instance().get(key))
+}
+
+/// Replaces a legacy single-vector index, moving it out of instance storage.
+fn set_legacy_ids(env: &Env, key: &DataKey, ids: &Vec<u64>) {
+    env.storage().persistent().set(key, ids);
+    extend_persistent(env, key);
+    env.storage().instance().remove(key);
+}
+
+/// Copies the next `INDEX_MIGRATION_CHUNK` entries of a legacy single-vector
+/// index to counter-plus-entries form. The legacy vector stays authoritative
+/// until the last chunk lands, so it is returned while the copy is unfinished.
+pub(crate) fn migrate_legacy_index(
+    env: &Env,
+    index: UserIndex,
+    user: &Address,
+) -> Option<Vec<u64>> {
+    let legacy_key = index.legacy_key(user);
+    let ids = legacy_ids(env, &legacy_key)?;
+
+    let cursor_key = index.legacy_cursor_key(user);
+    let cursor: u32 = env.storage().persistent().get(&cursor_key).unwrap_or(0);
+    let end = ids.len().min(cursor.saturating_add(INDEX_MIGRATION_CHUNK));
+    for position in cursor..end {
+        let entry_key = index.entry_key(user, position);
+        env.storage().persistent().set(&entry_key, &ids.get_unchecked(position));
+        extend_persistent(env, &entry_key);
+    }
+
+    if end < ids.len() {
+        env.storage().persistent().set(&cursor_key, &end);
+        extend_persistent(env, &cursor_key);
+        return Some(ids);
+    }
+
+    let count_key = index.count_key(user);
+    env.storage().persistent().set(&count_key, &ids.len());
+    extend_persistent(env, &count_key);
+
+    env.storage().persistent().remove(&cursor_key);
+    env.storage().persistent().remove(&legacy_key);
+    env.storage().instance().remove(&legacy_key);
+    None
+}
+
+pub(crate) fn index_len(env: &Env, index: UserIndex, user: &Address) -> u32 {
+    let count_key = index.count_key(user);
+    if let Some(count) = env.storage().persistent().get(&count_key) {
+        extend_persistent(env, &count_key);
+        return count;
+    }
+    legacy_ids(env, &index.legacy_key(user)).map_or(0, |ids| ids.len())
+}
+
+/// Returns up to `limit` ids starting at `cursor`, in creation order.
+pub(crate) fn index_range(
+    env: &Env,
+    index: UserIndex,
+    user: &Address,
+    cursor: u32,
+    limit: u32,
+) -> Vec<u64> {
+    let end = index_len(env, index, user).min(cursor.saturating_add(limit));
+    let mut ids = Vec::new(env);
+
+    if !env.storage().persistent().has(&index.count_key(user)) {
+        if let Some(legacy) = legacy_ids(env, &index.legacy_key(user)) {
+            for position in cursor..end {
+                ids.push_back(legacy.get_unchecked(position));
+            }
+        }
+        return ids;
+    }
+
+    for position in cursor..end {
+        let entry_key = index.entry_key(user, position);
+        let id: u64 = env.storage().persistent().get(&entry_key).unwrap();
+        extend_persistent(env, &entry_key);
+        ids.push_back(id);
+    }
+    ids
+}
+
+/// Removes `id` from the index, keeping the remaining ids in order. The search
+/// starts from the newest entry, so dropping a recent id touches few entries.
+pub(crate) fn index_remove(env: &Env, index: UserIndex, user: &Address, id: u64) {
+    if let Some(mut ids) = migrate_legacy_index(env, index, user) {
+        let Some(position) = ids.last_index_of(id) else {
+            return;
+        };
+        ids.remove(position);
+        set_legacy_ids(env, &index.legacy_key(user), &ids);
+
+        // Entries from `position` on have shifted and must be copied again.
+        let cursor_key = index.legacy_cursor_key(user);
+        let cursor: u32 = env.storage().persistent().get(&cursor_key).unwrap_or(0);
+        env.storage().persistent().set(&cursor_key, &cursor.min(position));
+        extend_persistent(env, &cursor_key);
+        return;
+    }
+
+    let count = index_len(env, index, user);
+    let Some(position) = (0..count).rev().find(|&position| {
+        let entry: u64 = env
+            .storage()
+            .persistent()
+            .get(&index.entry_key(user, position))
+            .unwrap();
+        entry == id
+    }) else {
+        return;
+    };
+
+    for position in position..count - 1 {
+        let next: u64 = env
+            .storage()
+            .persistent()
+            .get(&index.entry_key(user, position + 1))
+            .unwrap();
+        let entry_key = index.entry_key(user, position);
+        env.storage().persistent().set(&entry_key, &next);
+        extend_persistent(env, &entry_key);
+    }
+    env.storage()
+        .persistent()
+        .remove(&index.entry_key(user, count - 1));
+
+    let count_key = index.count_key(user);
+    env.storage().persistent().set(&count_key, &(count - 1));
+    extend_persistent(env, &count_key);
+}
+
+pub(crate) fn index_push(env: &Env, index: UserIndex, user: &Address, id: u64) {
+    if let Some(mut ids) = migrate_legacy_index(env, index, user) {
+        ids.push_back(id);
+        set_legacy_ids(env, &index.legacy_key(user), &ids);
+        return;
+    }
+
+    let count = index_len(env, index, user);
+    let entry_key = index.entry_key(user, count);
+    env.storage().persistent().set(&entry_key, &id);
+    extend_persistent(env, &entry_key);
+
+    let count_key = index.count_key(user);
+    env.storage().persistent().set(&count_key, &(count + 1));
+    extend_persistent(env, &count_key);
+}
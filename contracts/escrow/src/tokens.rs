@@ -0,0 +1,84 @@
+use soroban_sdk::{contracttype, Address, Env, Vec};
+
+use crate::{admin, DataKey, EscrowError};
+
+/// Bounds on the amount a single escrow may lock in a listed token.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[contracttype]
+pub struct TokenLimits {
+    pub min_amount: Option<i128>,
+    pub max_amount: Option<i128>,
+}
+
+impl TokenLimits {
+    fn validate(&self) -> Result<(), EscrowError> {
+        if self.min_amount.is_some_and(|min| min <= 0)
+            || self.max_amount.is_some_and(|max| max <= 0)
+        {
+            return Err(EscrowError::NonPositiveAmount);
+        }
+        if let (Some(min), Some(max)) = (self.min_amount, self.max_amount) {
+            if min > max {
+                return Err(EscrowError::BadTokenLimits);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn supported(env: &Env) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&DataKey::SupportedTokens)
+        .unwrap_or_else(|| Vec::new(env))
+}
+
+pub(crate) fn get_limits(env: &Env, token: &Address) -> Option<TokenLimits> {
+    env.storage()
+        .instance()
+        .get(&DataKey::TokenLimits(token.clone()))
+}
+
+/// Lists `token`, or updates its limits if already listed.
+pub(crate) fn set(env: &Env, token: &Address, limits: &TokenLimits) -> Result<(), EscrowError> {
+    limits.validate()?;
+
+    let mut tokens = supported(env);
+    if !tokens.contains(token) {
+        tokens.push_back(token.clone());
+        env.storage().instance().set(&DataKey::SupportedTokens, &tokens);
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::TokenLimits(token.clone()), limits);
+    Ok(())
+}
+
+pub(crate) fn remove(env: &Env, token: &Address) -> Result<(), EscrowError> {
+    let mut tokens = supported(env);
+    let position = tokens
+        .first_index_of(token)
+        .ok_or(EscrowError::TokenNotAllowed)?;
+    tokens.remove(position);
+
+    env.storage().instance().set(&DataKey::SupportedTokens, &tokens);
+    env.storage()
+        .instance()
+        .remove(&DataKey::TokenLimits(token.clone()));
+    Ok(())
+}
+
+/// Rejects unlisted tokens and amounts outside the token's limits. Without an
+/// admin to list tokens, nothing is allowed.
+pub(crate) fn check(env: &Env, token: &Address, amount: i128) -> Result<(), EscrowError> {
+    admin::get_admin(env)?;
+
+    let limits = get_limits(env, token).ok_or(EscrowError::TokenNotAllowed)?;
+    if limits.min_amount.is_some_and(|min| amount < min) {
+        return Err(EscrowError::AmountBelowMin);
+    }
+    if limits.max_amount.is_some_and(|max| amount > max) {
+        return Err(EscrowError::AmountAboveMax);
+    }
+    Ok(())
+}
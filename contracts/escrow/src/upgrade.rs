@@ -0,0 +1,65 @@
+use soroban_sdk::Env;
+
+use crate::{events, storage, storage::UserIndex, DataKey, EscrowError};
+
+/// Layout of the records written by this build.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// First release: `u32` status codes, percentage splits, instance-storage records.
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+
+/// Escrows rewritten per `migrate` call, so each call stays within budget.
+pub const MIGRATION_BATCH_SIZE: u64 = 25;
+
+/// Contracts that hold escrows but never recorded a version predate versioning.
+pub(crate) fn schema_version(env: &Env) -> u32 {
+    let instance = env.storage().instance();
+    instance.get(&DataKey::SchemaVersion).unwrap_or_else(|| {
+        if instance.has(&DataKey::NextId) {
+            LEGACY_SCHEMA_VERSION
+        } else {
+            SCHEMA_VERSION
+        }
+    })
+}
+
+pub(crate) fn set_schema_version(env: &Env, version: u32) {
+    env.storage().instance().set(&DataKey::SchemaVersion, &version);
+}
+
+/// Rewrites the next batch of escrows in the current layout, advancing the
+/// migration of their parties' user indexes. Records that fail to decode are
+/// left in place and reported. Returns `true` once every escrow has been
+/// visited and the version recorded.
+pub(crate) fn migrate_batch(env: &Env) -> Result<bool, EscrowError> {
+    let last: u64 = env.storage().instance().get(&DataKey::NextId).unwrap_or(0);
+    let cursor: u64 = env
+        .storage()
+        .instance()
+        .get(&DataKey::MigrationCursor)
+        .unwrap_or(0);
+    let end = last.min(cursor.saturating_add(MIGRATION_BATCH_SIZE));
+
+    for id in cursor + 1..=end {
+        match storage::load_escrow(env, id) {
+            Ok(data) => {
+                storage::save_escrow(env, id, &data);
+                storage::migrate_legacy_index(env, UserIndex::Created, &data.sender);
+                for (recipient, _) in data.recipients.iter() {
+                    storage::migrate_legacy_index(env, UserIndex::Received, &recipient);
+                }
+            }
+            Err(EscrowError::NotFound) => {}
+            // An unreadable record must not hold up the rest of the contract.
+            Err(err) => events::migration_skipped(env, id, err),
+        }
+    }
+
+    if end < last {
+        env.storage().instance().set(&DataKey::MigrationCursor, &end);
+        return Ok(false);
+    }
+    env.storage().instance().remove(&DataKey::MigrationCursor);
+    set_schema_version(env, SCHEMA_VERSION);
+    Ok(true)
+}
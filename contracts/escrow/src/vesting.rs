@@ -0,0 +1,158 @@
+use soroban_sdk::{contracttype, Env, Vec};
+
+use crate::{split, EscrowData, EscrowError, EscrowStatus};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Vesting {
+    pub start:      u64,
+    /// Nothing vests before this; zero means no cliff.
+    pub cliff:      u64,
+    pub end:        u64,
+    /// Set when the sender stops the stream; nothing vests after it.
+    pub stopped_at: Option<u64>,
+    /// Parallel to the escrow's `recipients`: amounts withdrawn so far.
+    pub withdrawn:  Vec<i128>,
+}
+
+pub(crate) fn build(
+    env: &Env,
+    start: u64,
+    cliff: u64,
+    end: u64,
+    recipient_count: u32,
+) -> Result<Vesting, EscrowError> {
+    if start >= end || (cliff != 0 && (cliff < start || cliff > end)) {
+        return Err(EscrowError::BadSchedule);
+    }
+    if end <= env.ledger().timestamp() {
+        return Err(EscrowError::InvalidDeadline);
+    }
+
+    let mut withdrawn = Vec::new(env);
+    for _ in 0..recipient_count {
+        withdrawn.push_back(0);
+    }
+    Ok(Vesting {
+        start,
+        cliff,
+        end,
+        stopped_at: None,
+        withdrawn,
+    })
+}
+
+/// Rejects streams where a recipient's share rounds down to nothing: such a
+/// recipient could never withdraw, so the stream would never finish.
+pub(crate) fn check_shares(env: &Env, data: &EscrowData) -> Result<(), EscrowError> {
+    if data.vesting().is_some() && data.shares(env).iter().any(|(_, share)| share == 0) {
+        return Err(EscrowError::ZeroShare);
+    }
+    Ok(())
+}
+
+/// Portion of `share` vested at `time`.
+fn vested_at(vesting: &Vesting, share: i128, time: u64) -> i128 {
+    if time < vesting.start || time < vesting.cliff {
+        return 0;
+    }
+    if time >= vesting.end {
+        return share;
+    }
+
+    split::mul_div(share, time - vesting.start, vesting.end - vesting.start)
+}
+
+/// What recipient `index` may have withdrawn by now: their vested share,
+/// frozen at the moment the stream was stopped.
+fn entitled(env: &Env, data: &EscrowData, vesting: &Vesting, index: u32) -> i128 {
+    let (_, share) = data.shares(env).get_unchecked(index);
+    let now = env.ledger().timestamp();
+    vested_at(vesting, share, vesting.stopped_at.map_or(now, |stop| stop.min(now)))
+}
+
+/// Whether nothing more can vest.
+fn is_final(env: &Env, vesting: &Vesting) -> bool {
+    vesting.stopped_at.is_some() || env.ledger().timestamp() >= vesting.end
+}
+
+/// Pays recipient `index` everything vested since their last withdrawal and
+/// returns the amount.
+pub(crate) fn withdraw(env: &Env, data: &mut EscrowData, index: u32) -> Result<i128, EscrowError> {
+    data.status.ensure_active()?;
+    let Some(mut vesting) = data.vesting() else {
+        return Err(EscrowError::InvalidState);
+    };
+
+    let entitled = entitled(env, data, &vesting, index);
+    let amount = entitled - vesting.withdrawn.get_unchecked(index);
+    if amount <= 0 {
+        return Err(EscrowError::NothingToClaim);
+    }
+
+    vesting.withdrawn.set(index, entitled);
+    if is_final(env, &vesting) {
+        data.claimed.set(index, true);
+    }
+    data.set_vesting(vesting);
+    settle(data)?;
+    Ok(amount)
+}
+
+/// Stops the stream and returns the unvested remainder owed to the sender.
+pub(crate) fn stop(env: &Env, data: &mut EscrowData) -> Result<i128, EscrowError> {
+    data.status.ensure_active()?;
+    let Some(mut vesting) = data.vesting() else {
+        return Err(EscrowError::InvalidState);
+    };
+    if is_final(env, &vesting) {
+        return Err(EscrowError::InvalidState);
+    }
+
+    vesting.stopped_at = Some(env.ledger().timestamp());
+    let mut unvested: i128 = 0;
+    for (index, (_, share)) in data.shares(env).iter().enumerate() {
+        let index = index as u32;
+        let entitled = entitled(env, data, &vesting, index);
+        unvested += share - entitled;
+        if vesting.withdrawn.get_unchecked(index) == entitled {
+            data.claimed.set(index, true);
+        }
+    }
+    data.set_vesting(vesting);
+    settle(data)?;
+    Ok(unvested)
+}
+
+/// Funds still owed to recipients, plus the unvested rest unless stopped.
+pub(crate) fn locked_amount(env: &Env, data: &EscrowData, vesting: &Vesting) -> i128 {
+    let mut total: i128 = 0;
+    for (index, (_, share)) in data.shares(env).iter().enumerate() {
+        let index = index as u32;
+        let owed = if vesting.stopped_at.is_some() {
+            entitled(env, data, vesting, index)
+        } else {
+            share
+        };
+        total += owed - vesting.withdrawn.get_unchecked(index);
+    }
+    total
+}
+
+/// Moves the escrow to the status implied by its withdrawals.
+fn settle(data: &mut EscrowData) -> Result<(), EscrowError> {
+    let any_withdrawn = data
+        .vesting()
+        .is_some_and(|vesting| vesting.withdrawn.iter().any(|amount| amount > 0));
+
+    let next = match (data.claimed.iter().all(|c| c), any_withdrawn) {
+        (true, true) => EscrowStatus::Claimed,
+        (true, false) => EscrowStatus::Refunded,
+        (false, true) => EscrowStatus::PartiallyClaimed,
+        (false, false) => data.status,
+    };
+    if next != data.status {
+        data.status = data.status.transition(next)?;
+    }
+    Ok(())
+}
@@ -1,11 +1,25 @@
+use proptest::prelude::*;
 use soroban_sdk::{
-    testutils::Address as _,
-    token, Address, Env, Vec,
+    symbol_short,
+    testutils::{
+        storage::Persistent as _, Address as _, EnvTestConfig, Events, Ledger, MockAuth,
+        MockAuthInvoke,
+    },
+    token, vec, Address, Bytes, BytesN, Env, IntoVal, Symbol, Val, Vec,
 };
 
-use p2p::{Escrow, EscrowClient};
+use p2p::{
+    DataKey, Escrow, EscrowClient, EscrowError, EscrowOptions, EscrowStatus, FeeConfig,
+    LegacyEscrowData, MilestoneStatus, PauseFlags, ReleasePolicy, Role, TokenLimits,
+    BPS_DENOMINATOR, INDEX_MIGRATION_CHUNK, LEGACY_SCHEMA_VERSION, MAX_FEE_BPS, MAX_PAGE_SIZE,
+    MIGRATION_BATCH_SIZE, SCHEMA_VERSION,
+};
+
+/// Release build of this contract, the target of the upgrade test. Rebuild
+/// with `cargo build --target wasm32v1-none --release -p p2p`.
+const ESCROW_WASM: &[u8] = include_bytes!("fixtures/p2p.wasm");
 
-fn create_token<'a>(env: &'a Env, admin: &Address) -> (Address, token::Client<'a>) {
+fn create_unlisted_token<'a>(env: &'a Env, admin: &Address) -> (Address, token::Client<'a>) {
     let asset = env.register_stellar_asset_contract_v2(admin.clone());
     let admin_client = token::StellarAssetClient::new(env, &asset.address());
     admin_client.mint(admin, &10_000_000i128);
@@ -13,17 +27,157 @@ fn create_token<'a>(env: &'a Env, admin: &Address) -> (Address, token::Client<'a
     (asset.address(), client)
 }
 
+/// Creates a token and lists it, without limits, on `escrow`.
+fn create_token<'a>(
+    env: &'a Env,
+    escrow: &EscrowClient,
+    admin: &Address,
+) -> (Address, token::Client<'a>) {
+    let (address, client) = create_unlisted_token(env, admin);
+    escrow.set_token(&address, &TokenLimits::default());
+    (address, client)
+}
+
+/// Token that burns 1% of every transfer, and whose balances can be cut to
+/// mimic a negative rebase.
+mod fee_token {
+    use soroban_sdk::{contract, contractimpl, contracttype, Address, Env};
+
+    #[contracttype]
+    enum Key {
+        Balance(Address),
+    }
+
+    fn set_balance(env: &Env, id: &Address, amount: i128) {
+        env.storage().persistent().set(&Key::Balance(id.clone()), &amount);
+    }
+
+    #[contract]
+    pub struct FeeToken;
+
+    #[contractimpl]
+    impl FeeToken {
+        pub fn mint(env: Env, to: Address, amount: i128) {
+            set_balance(&env, &to, Self::balance(env.clone(), to.clone()) + amount);
+        }
+
+        pub fn rebase_down(env: Env, id: Address, amount: i128) {
+            set_balance(&env, &id, Self::balance(env.clone(), id.clone()) - amount);
+        }
+
+        pub fn balance(env: Env, id: Address) -> i128 {
+            env.storage().persistent().get(&Key::Balance(id)).unwrap_or(0)
+        }
+
+        pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+            from.require_auth();
+            let fee = amount / 100;
+            set_balance(&env, &from, Self::balance(env.clone(), from.clone()) - amount);
+            set_balance(&env, &to, Self::balance(env.clone(), to.clone()) + amount - fee);
+        }
+    }
+}
+
+/// Delivery-tracking oracle whose verdicts are set by the test. It can also
+/// approve escrows naming it as their approver.
+mod oracle {
+    use soroban_sdk::{contract, contractimpl, contracttype, Address, Env};
+
+    #[contracttype]
+    enum Key {
+        Fulfilled(u64),
+    }
+
+    #[contract]
+    pub struct MockOracle;
+
+    #[contractimpl]
+    impl MockOracle {
+        pub fn set_fulfilled(env: Env, id: u64, fulfilled: bool) {
+            env.storage().persistent().set(&Key::Fulfilled(id), &fulfilled);
+        }
+
+        pub fn is_fulfilled(env: Env, id: u64) -> bool {
+            env.storage().persistent().get(&Key::Fulfilled(id)).unwrap_or(false)
+        }
+
+        pub fn confirm_delivery(env: Env, escrow: Address, id: u64) {
+            Self::set_fulfilled(env.clone(), id, true);
+            p2p::EscrowClient::new(&env, &escrow).approve(&id, &env.current_contract_address());
+        }
+    }
+}
+
+fn create_fee_token<'a>(
+    env: &'a Env,
+    escrow: &EscrowClient,
+    holder: &Address,
+) -> (Address, fee_token::FeeTokenClient<'a>) {
+    let address = env.register(fee_token::FeeToken, ());
+    let client = fee_token::FeeTokenClient::new(env, &address);
+    client.mint(holder, &10_000_000i128);
+    escrow.set_token(&address, &TokenLimits::default());
+    (address, client)
+}
+
+fn salt(env: &Env) -> BytesN<32> {
+    BytesN::from_array(env, &[0u8; 32])
+}
+
+/// Registers the contract where `deployer` would have deployed it with
+/// `salt`, leaving it uninitialized.
+fn deploy_escrow_contract<'a>(env: &'a Env, deployer: &Address) -> (Address, EscrowClient<'a>) {
+    let address = env.deployer().with_address(deployer.clone(), salt(env)).deployed_address();
+    let contract_id = env.register_at(&address, Escrow, ());
+    let client = EscrowClient::new(env, &contract_id);
+    (contract_id, client)
+}
+
+/// Deploys and initializes the contract; its deployer becomes the admin.
+fn create_escrow_contract<'a>(env: &'a Env) -> (Address, EscrowClient<'a>) {
+    let admin = Address::generate(env);
+    let (contract_id, client) = deploy_escrow_contract(env, &admin);
+    client.initialize(&admin, &admin, &salt(env));
+    (contract_id, client)
+}
+
+fn single_recipient(env: &Env, recipient: &Address) -> Vec<(Address, u32)> {
+    let mut recipients: Vec<(Address, u32)> = Vec::new(env);
+    recipients.push_back((recipient.clone(), 100u32));
+    recipients
+}
+
+/// Asserts that the last event of the latest invocation came from
+/// `contract_id` with these topics and data. The host only keeps the events of
+/// the latest invocation, so call this straight after the one being checked.
+fn assert_last_event(
+    env: &Env,
+    contract_id: &Address,
+    topics: impl IntoVal<Env, Vec<Val>>,
+    data: impl IntoVal<Env, Val>,
+) {
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![env, (contract_id.clone(), topics.into_val(env), data.into_val(env))]
+    );
+}
+
+/// Addresses that authorized the latest invocation, in order.
+fn signers(env: &Env) -> std::vec::Vec<Address> {
+    env.auths().into_iter().map(|(address, _)| address).collect()
+}
+
 #[test]
 fn test_create_and_get_escrow() {
     let env = Env::default();
     env.mock_all_auths();
 
-    let contract_id = env.register_contract(None, Escrow);
-    let client = EscrowClient::new(&env, &contract_id);
+    let (_, client) = create_escrow_contract(&env);
 
     let sender = Address::generate(&env);
     let recipient = Address::generate(&env);
-    let (token_id, _) = create_token(&env, &sender);
+    let (token_id, _) = create_token(&env, &client, &sender);
 
     let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
     recipients.push_back((recipient.clone(), 100u32));
@@ -35,8 +189,7 @@ fn test_create_and_get_escrow() {
     let escrow = client.get_escrow(&id);
     assert_eq!(escrow.amount, 1_000_000i128);
     assert_eq!(escrow.sender, sender);
-    assert_eq!(escrow.approved, false);
-    assert_eq!(escrow.status, 0u32);
+    assert_eq!(escrow.status, EscrowStatus::Pending);
 }
 
 #[test]
@@ -44,42 +197,2591 @@ fn test_create_approve_claim_single_recipient() {
     let env = Env::default();
     env.mock_all_auths();
 
-    let contract_id = env.register_contract(None, Escrow);
-    let client = EscrowClient::new(&env, &contract_id);
+    let (contract_id, client) = create_escrow_contract(&env);
 
     let sender = Address::generate(&env);
     let recipient = Address::generate(&env);
-    let (token_id, token) = create_token(&env, &sender);
+    let (token_id, token) = create_token(&env, &client, &sender);
 
     let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
     recipients.push_back((recipient.clone(), 100u32));
 
     let id = client.create(&sender, &recipients, &1_000_000i128, &token_id, &0u64);
-    client.approve(&id);
+    client.approve(&id, &sender);
     client.claim(&id, &recipient);
 
     assert_eq!(token.balance(&recipient), 1_000_000i128);
     assert_eq!(token.balance(&contract_id), 0i128);
 
     let data = client.get_escrow(&id);
-    assert_eq!(data.status, 2u32);
+    assert_eq!(data.status, EscrowStatus::Claimed);
 }
 
 #[test]
-#[should_panic(expected = "percentages must sum to 100")]
 fn test_create_fails_on_invalid_percentage_sum() {
     let env = Env::default();
     env.mock_all_auths();
 
-    let contract_id = env.register_contract(None, Escrow);
-    let client = EscrowClient::new(&env, &contract_id);
+    let (_, client) = create_escrow_contract(&env);
 
     let sender = Address::generate(&env);
     let recipient = Address::generate(&env);
-    let (token_id, _) = create_token(&env, &sender);
+    let (token_id, _) = create_token(&env, &client, &sender);
 
     let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
     recipients.push_back((recipient.clone(), 90u32));
 
-    client.create(&sender, &recipients, &1_000_000i128, &token_id, &0u64);
-}
\ No newline at end of file
+    let result = client.try_create(&sender, &recipients, &1_000_000i128, &token_id, &0u64);
+    assert_eq!(result, Err(Ok(EscrowError::BadPercentages)));
+}
+
+#[test]
+fn test_create_rejects_invalid_input() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let result = client.try_create(&sender, &recipients, &0i128, &token_id, &0u64);
+    assert_eq!(result, Err(Ok(EscrowError::NonPositiveAmount)));
+
+    let result = client.try_create(&sender, &Vec::new(&env), &1_000i128, &token_id, &0u64);
+    assert_eq!(result, Err(Ok(EscrowError::NoRecipients)));
+
+    let mut overflowing: Vec<(Address, u32)> = Vec::new(&env);
+    overflowing.push_back((recipient.clone(), u32::MAX));
+    overflowing.push_back((Address::generate(&env), 101u32));
+    let result = client.try_create(&sender, &overflowing, &1_000i128, &token_id, &0u64);
+    assert_eq!(result, Err(Ok(EscrowError::BadPercentages)));
+
+    let duplicated = vec![&env, (recipient.clone(), 50u32), (recipient.clone(), 50u32)];
+    let result = client.try_create(&sender, &duplicated, &1_000i128, &token_id, &0u64);
+    assert_eq!(result, Err(Ok(EscrowError::DuplicateRecipient)));
+
+    let zero = vec![&env, (recipient.clone(), 100u32), (Address::generate(&env), 0u32)];
+    let result = client.try_create(&sender, &zero, &1_000i128, &token_id, &0u64);
+    assert_eq!(result, Err(Ok(EscrowError::ZeroShare)));
+}
+
+#[test]
+fn test_missing_escrow_returns_not_found() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+    let caller = Address::generate(&env);
+
+    assert_eq!(client.try_get_escrow(&7u64), Err(Ok(EscrowError::NotFound)));
+    assert_eq!(client.try_approve(&7u64, &caller), Err(Ok(EscrowError::NotFound)));
+    assert_eq!(client.try_claim(&7u64, &caller), Err(Ok(EscrowError::NotFound)));
+    assert_eq!(client.try_refund(&7u64), Err(Ok(EscrowError::NotFound)));
+}
+
+#[test]
+fn test_claim_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &100u64);
+
+    assert_eq!(client.try_claim(&id, &stranger), Err(Ok(EscrowError::NotRecipient)));
+    assert_eq!(client.try_claim(&id, &recipient), Err(Ok(EscrowError::NotApproved)));
+
+    client.approve(&id, &sender);
+    assert_eq!(client.try_approve(&id, &sender), Err(Ok(EscrowError::InvalidState)));
+
+    env.ledger().with_mut(|l| l.timestamp = 101);
+    assert_eq!(client.try_claim(&id, &recipient), Err(Ok(EscrowError::DeadlinePassed)));
+}
+
+#[test]
+fn test_refund_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &100u64);
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::DeadlineNotPassed)));
+
+    env.ledger().with_mut(|l| l.timestamp = 101);
+    client.refund(&id);
+    assert_eq!(token.balance(&sender), 10_000_000i128);
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::InvalidState)));
+}
+
+#[test]
+fn test_status_transitions() {
+    use EscrowStatus::*;
+
+    let allowed = [
+        (Pending, Approved),
+        (Pending, Refunded),
+        (Approved, Claimed),
+        (Approved, PartiallyClaimed),
+        (Approved, Refunded),
+        (PartiallyClaimed, Claimed),
+        (PartiallyClaimed, Refunded),
+        (Pending, Disputed),
+        (Approved, Disputed),
+        (PartiallyClaimed, Disputed),
+        (Disputed, Resolved),
+        (Pending, Cancelled),
+        (Approved, Cancelled),
+        (PartiallyClaimed, Cancelled),
+    ];
+    let all = [
+        Pending,
+        Approved,
+        Claimed,
+        Refunded,
+        PartiallyClaimed,
+        Disputed,
+        Resolved,
+        Cancelled,
+    ];
+
+    for from in all {
+        for to in all {
+            let result = from.transition(to);
+            if allowed.contains(&(from, to)) {
+                assert_eq!(result, Ok(to));
+            } else {
+                assert_eq!(result, Err(EscrowError::InvalidState));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_legacy_record_is_migrated_on_read() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    token.transfer(&sender, &contract_id, &1_000i128);
+
+    let legacy = LegacyEscrowData {
+        sender: sender.clone(),
+        recipients: single_recipient(&env, &recipient),
+        amount: 1_000i128,
+        token: token_id,
+        created_at: 0,
+        deadline: 0,
+        approved: true,
+        status: 1,
+    };
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&DataKey::Escrow(1), &legacy);
+        env.storage().instance().set(&DataKey::NextId, &1u64);
+    });
+
+    let data = client.get_escrow(&1u64);
+    assert_eq!(data.status, EscrowStatus::Approved);
+    assert_eq!(data.amount, 1_000i128);
+
+    client.claim(&1u64, &recipient);
+    assert_eq!(token.balance(&recipient), 1_000i128);
+    assert_eq!(client.get_escrow(&1u64).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_legacy_record_with_duplicate_and_zero_shares_is_migrated() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let idle = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    token.transfer(&sender, &contract_id, &1_000i128);
+
+    // Stored before `create` rejected repeated recipients and 0% shares.
+    let legacy = LegacyEscrowData {
+        sender: sender.clone(),
+        recipients: vec![
+            &env,
+            (first.clone(), 30u32),
+            (idle.clone(), 0u32),
+            (second.clone(), 50u32),
+            (first.clone(), 20u32),
+        ],
+        amount: 1_000i128,
+        token: token_id,
+        created_at: 0,
+        deadline: 0,
+        approved: true,
+        status: 1,
+    };
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&DataKey::Escrow(1), &legacy);
+        env.storage().instance().set(&DataKey::NextId, &1u64);
+    });
+
+    let data = client.get_escrow(&1u64);
+    assert_eq!(
+        data.recipients,
+        vec![&env, (first.clone(), 5_000u32), (second.clone(), 5_000u32)]
+    );
+    assert_eq!(data.claimed, vec![&env, false, false]);
+
+    client.claim(&1u64, &first);
+    client.claim(&1u64, &second);
+    assert_eq!(token.balance(&first), 500i128);
+    assert_eq!(token.balance(&second), 500i128);
+    assert_eq!(client.get_escrow(&1u64).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_records_live_in_persistent_storage_past_default_ttl() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+
+    env.as_contract(&contract_id, || {
+        assert!(!env.storage().instance().has(&DataKey::Escrow(id)));
+        assert!(env.storage().persistent().has(&DataKey::Escrow(id)));
+        assert!(env.storage().persistent().has(&DataKey::UserCreatedCount(sender.clone())));
+        assert!(env.storage().persistent().get_ttl(&DataKey::Escrow(id)) > 4_096);
+    });
+
+    // Well past the default minimum persistent TTL of 4_096 ledgers.
+    env.ledger().with_mut(|l| l.sequence_number += 100_000);
+
+    assert_eq!(client.get_escrow(&id).amount, 1_000i128);
+    assert_eq!(client.get_created_ids(&sender).len(), 1);
+    assert_eq!(client.get_received_ids(&recipient).len(), 1);
+}
+
+#[test]
+fn test_bump_extends_escrow_ttl() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    let ttl = || {
+        env.as_contract(&contract_id, || {
+            env.storage().persistent().get_ttl(&DataKey::Escrow(id))
+        })
+    };
+    let ttl_at_create = ttl();
+
+    env.ledger().with_mut(|l| l.sequence_number += 100_000);
+    let ttl_before_bump = ttl();
+    assert!(ttl_before_bump < ttl_at_create);
+
+    client.bump(&id);
+    assert_eq!(ttl(), ttl_at_create);
+
+    assert_eq!(client.try_bump(&99u64), Err(Ok(EscrowError::NotFound)));
+}
+
+#[test]
+fn test_created_and_received_pages() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    for amount in 1..=5i128 {
+        client.create(&sender, &recipients, &amount, &token_id, &0u64);
+    }
+
+    let first = client.get_created_page(&sender, &0u32, &2u32);
+    assert_eq!(first.entries.len(), 2);
+    assert_eq!(first.entries.get(0).unwrap().0, 1u64);
+    assert_eq!(first.entries.get(1).unwrap().1.amount, 2i128);
+    assert_eq!(first.next_cursor, Some(2u32));
+
+    let second = client.get_created_page(&sender, &2u32, &2u32);
+    assert_eq!(second.entries.get(0).unwrap().0, 3u64);
+    assert_eq!(second.next_cursor, Some(4u32));
+
+    let last = client.get_received_page(&recipient, &4u32, &2u32);
+    assert_eq!(last.entries.len(), 1);
+    assert_eq!(last.entries.get(0).unwrap().0, 5u64);
+    assert_eq!(last.next_cursor, None);
+
+    let past_end = client.get_received_page(&recipient, &9u32, &2u32);
+    assert_eq!(past_end.entries.len(), 0);
+    assert_eq!(past_end.next_cursor, None);
+
+    assert_eq!(
+        client.try_get_created_page(&sender, &0u32, &0u32),
+        Err(Ok(EscrowError::InvalidPageSize))
+    );
+    assert_eq!(
+        client.try_get_created_page(&sender, &0u32, &(MAX_PAGE_SIZE + 1)),
+        Err(Ok(EscrowError::InvalidPageSize))
+    );
+}
+
+#[test]
+fn test_legacy_vector_index_is_migrated_on_write() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let first = client.create(&sender, &recipients, &10i128, &token_id, &0u64);
+    let second = client.create(&sender, &recipients, &20i128, &token_id, &0u64);
+
+    // Rewind the sender's index to the layout used by earlier releases.
+    env.as_contract(&contract_id, || {
+        let storage = env.storage().persistent();
+        storage.remove(&DataKey::UserCreatedCount(sender.clone()));
+        storage.remove(&DataKey::UserCreatedEntry(sender.clone(), 0));
+        storage.remove(&DataKey::UserCreatedEntry(sender.clone(), 1));
+
+        let mut legacy: Vec<u64> = Vec::new(&env);
+        legacy.push_back(first);
+        legacy.push_back(second);
+        env.storage().instance().set(&DataKey::UserCreated(sender.clone()), &legacy);
+    });
+
+    assert_eq!(client.get_created_ids(&sender).len(), 2);
+    assert_eq!(client.get_created_page(&sender, &1u32, &5u32).entries.get(0).unwrap().0, second);
+
+    let third = client.create(&sender, &recipients, &30i128, &token_id, &0u64);
+
+    let mut expected: Vec<u64> = Vec::new(&env);
+    expected.push_back(first);
+    expected.push_back(second);
+    expected.push_back(third);
+    assert_eq!(client.get_created_ids(&sender), expected);
+
+    env.as_contract(&contract_id, || {
+        assert!(!env.storage().instance().has(&DataKey::UserCreated(sender.clone())));
+    });
+}
+
+#[test]
+fn test_id_lists_stop_at_page_size() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+    let user = Address::generate(&env);
+
+    let mut ids: Vec<u64> = Vec::new(&env);
+    for id in 1..=(MAX_PAGE_SIZE as u64 + 1) {
+        ids.push_back(id);
+    }
+    env.as_contract(&contract_id, || {
+        env.storage().persistent().set(&DataKey::UserCreated(user.clone()), &ids);
+    });
+
+    assert_eq!(client.get_created_ids(&user), ids.slice(..MAX_PAGE_SIZE));
+    assert_eq!(client.get_received_ids(&user).len(), 0);
+}
+
+#[test]
+fn test_large_legacy_index_is_migrated_in_chunks() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let accepting = EscrowOptions { require_acceptance: true, ..Default::default() };
+
+    let legacy_len = 2 * INDEX_MIGRATION_CHUNK + 5;
+    let mut expected: Vec<u64> = Vec::new(&env);
+    for position in 0..legacy_len {
+        expected.push_back(1_000 + position as u64);
+    }
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&DataKey::UserReceived(recipient.clone()), &expected);
+    });
+    let cursor = || {
+        env.as_contract(&contract_id, || {
+            env.storage()
+                .persistent()
+                .get::<_, u32>(&DataKey::UserReceivedCursor(recipient.clone()))
+        })
+    };
+
+    // Each write copies one chunk and, until the last, lands on the legacy vector.
+    let rejected = client.create_v2(&sender, &recipients, &10i128, &token_id, &0u64, &accepting);
+    assert_eq!(cursor(), Some(INDEX_MIGRATION_CHUNK));
+    let mut with_rejected = expected.clone();
+    with_rejected.push_back(rejected);
+    assert_eq!(client.get_received_ids(&recipient), with_rejected);
+
+    client.reject(&rejected, &recipient);
+    assert_eq!(cursor(), Some(2 * INDEX_MIGRATION_CHUNK));
+    assert_eq!(client.get_received_ids(&recipient), expected);
+
+    let id = client.create_v2(&sender, &recipients, &10i128, &token_id, &0u64, &accepting);
+    assert_eq!(cursor(), None);
+    expected.push_back(id);
+    assert_eq!(client.get_received_ids(&recipient), expected);
+
+    env.as_contract(&contract_id, || {
+        assert!(!env.storage().instance().has(&DataKey::UserReceived(recipient.clone())));
+        assert!(!env.storage().persistent().has(&DataKey::UserReceived(recipient.clone())));
+        let count: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::UserReceivedCount(recipient.clone()))
+            .unwrap();
+        assert_eq!(count, legacy_len + 1);
+    });
+}
+
+#[test]
+fn test_claim_assigns_rounding_dust_to_last_recipient() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    let c = Address::generate(&env);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((a.clone(), 33u32));
+    recipients.push_back((b.clone(), 33u32));
+    recipients.push_back((c.clone(), 34u32));
+
+    let id = client.create(&sender, &recipients, &1_000_001i128, &token_id, &0u64);
+    client.approve(&id, &sender);
+    client.claim(&id, &a);
+    client.claim(&id, &b);
+    client.claim(&id, &c);
+
+    assert_eq!(token.balance(&a), 330_000i128);
+    assert_eq!(token.balance(&b), 330_000i128);
+    assert_eq!(token.balance(&c), 340_001i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn prop_claim_leaves_no_dust(
+        amount in 1i128..=10_000_000i128,
+        mut cuts in prop::collection::vec(1u32..100u32, 0..5),
+    ) {
+        // Random cases would churn test_snapshots on every run.
+        let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+        env.mock_all_auths();
+
+        let (contract_id, client) = create_escrow_contract(&env);
+
+        let sender = Address::generate(&env);
+        let (token_id, token) = create_token(&env, &client, &sender);
+
+        // Zero shares are rejected, so cuts must be distinct.
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts.push(100);
+        let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+        let mut previous = 0u32;
+        for cut in cuts {
+            recipients.push_back((Address::generate(&env), cut - previous));
+            previous = cut;
+        }
+
+        let id = client.create(&sender, &recipients, &amount, &token_id, &0u64);
+        client.approve(&id, &sender);
+        for (recipient, _) in recipients.iter() {
+            client.claim(&id, &recipient);
+        }
+
+        let paid: i128 = recipients.iter().map(|(r, _)| token.balance(&r)).sum();
+        prop_assert_eq!(paid, amount);
+        prop_assert_eq!(token.balance(&contract_id), 0i128);
+    }
+}
+
+#[test]
+fn test_create_v2_splits_by_basis_points() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let freelancer = Address::generate(&env);
+    let platform = Address::generate(&env);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((freelancer.clone(), 9_750u32));
+    recipients.push_back((platform.clone(), 250u32));
+
+    let options = EscrowOptions::default();
+    let id = client.create_v2(&sender, &recipients, &1_000_000i128, &token_id, &0u64, &options);
+    client.approve(&id, &sender);
+    client.claim(&id, &freelancer);
+    client.claim(&id, &platform);
+
+    assert_eq!(token.balance(&freelancer), 975_000i128);
+    assert_eq!(token.balance(&platform), 25_000i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+}
+
+#[test]
+fn test_create_stores_percentages_as_basis_points() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    let stored = client.get_escrow(&id).recipients;
+    assert_eq!(stored.get(0).unwrap(), (recipient, BPS_DENOMINATOR));
+}
+
+#[test]
+fn test_create_v2_rejects_invalid_shares() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+
+    let shares = |entries: &[(&Address, u32)]| {
+        let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+        for (address, bps) in entries {
+            recipients.push_back(((*address).clone(), *bps));
+        }
+        recipients
+    };
+
+    let options = EscrowOptions::default();
+    let cases = [
+        (shares(&[(&a, 5_000), (&b, 4_000)]), EscrowError::BadShares),
+        (shares(&[(&a, 10_000), (&b, 0)]), EscrowError::ZeroShare),
+        (shares(&[(&a, 5_000), (&a, 5_000)]), EscrowError::DuplicateRecipient),
+        (shares(&[(&a, u32::MAX), (&b, 10_001)]), EscrowError::BadShares),
+        (shares(&[]), EscrowError::NoRecipients),
+    ];
+    for (recipients, expected) in cases {
+        let result =
+            client.try_create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+        assert_eq!(result, Err(Ok(expected)));
+    }
+}
+
+#[test]
+fn test_recipients_claim_their_own_share_independently() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((a.clone(), 60u32));
+    recipients.push_back((b.clone(), 40u32));
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    client.approve(&id, &sender);
+
+    client.claim(&id, &a);
+    assert_eq!(token.balance(&a), 600i128);
+    assert_eq!(token.balance(&b), 0i128);
+    assert_eq!(token.balance(&contract_id), 400i128);
+
+    let data = client.get_escrow(&id);
+    assert_eq!(data.status, EscrowStatus::PartiallyClaimed);
+    assert_eq!(data.claimed, Vec::from_array(&env, [true, false]));
+    assert_eq!(client.try_claim(&id, &a), Err(Ok(EscrowError::AlreadyClaimed)));
+
+    client.claim(&id, &b);
+    assert_eq!(token.balance(&b), 400i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_refund_after_partial_claim_returns_unclaimed_shares() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((a.clone(), 25u32));
+    recipients.push_back((b.clone(), 75u32));
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &100u64);
+    client.approve(&id, &sender);
+    client.claim(&id, &a);
+
+    env.ledger().with_mut(|l| l.timestamp = 101);
+    client.refund(&id);
+
+    assert_eq!(token.balance(&a), 250i128);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 250i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Refunded);
+}
+
+#[test]
+fn test_milestones_release_in_order_and_claim_per_tranche() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((a.clone(), 5_000u32));
+    recipients.push_back((b.clone(), 5_000u32));
+    let milestones = vec![&env, (100i128, 0u64), (200i128, 0u64), (300i128, 0u64)];
+
+    let options = EscrowOptions::default();
+    let id = client.create_milestones(&sender, &recipients, &token_id, &milestones, &options);
+    assert_eq!(client.get_escrow(&id).amount, 600i128);
+    assert_eq!(token.balance(&contract_id), 600i128);
+    assert_eq!(client.try_claim(&id, &a), Err(Ok(EscrowError::NothingToClaim)));
+    assert_eq!(client.try_approve(&id, &sender), Err(Ok(EscrowError::InvalidState)));
+
+    client.approve_milestone(&id, &0u32);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("msrelease"), id),
+        (0u32, 100i128),
+    );
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Approved);
+    assert_eq!(
+        client.try_approve_milestone(&id, &2u32),
+        Err(Ok(EscrowError::MilestoneOutOfOrder))
+    );
+    assert_eq!(
+        client.try_approve_milestone(&id, &3u32),
+        Err(Ok(EscrowError::MilestoneNotFound))
+    );
+
+    client.claim(&id, &a);
+    assert_eq!(token.balance(&a), 50i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::PartiallyClaimed);
+
+    client.approve_milestone(&id, &1u32);
+    client.claim(&id, &b);
+    assert_eq!(token.balance(&b), 150i128);
+
+    let data = client.get_escrow(&id);
+    assert_eq!(data.milestones.get(0).unwrap().status, MilestoneStatus::Claimed);
+    assert_eq!(data.milestones.get(1).unwrap().status, MilestoneStatus::Released);
+
+    client.approve_milestone(&id, &2u32);
+    client.claim(&id, &a);
+    client.claim(&id, &b);
+    assert_eq!(token.balance(&a), 300i128);
+    assert_eq!(token.balance(&b), 300i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_unreleased_milestone_refund_after_deadline() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((recipient.clone(), BPS_DENOMINATOR));
+    let milestones = vec![&env, (400i128, 100u64), (600i128, 200u64)];
+
+    let options = EscrowOptions::default();
+    let id = client.create_milestones(&sender, &recipients, &token_id, &milestones, &options);
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(
+        client.try_refund_milestone(&id, &0u32),
+        Err(Ok(EscrowError::DeadlineNotPassed))
+    );
+
+    env.ledger().with_mut(|l| l.timestamp = 150);
+    client.refund_milestone(&id, &0u32);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 600i128);
+
+    // The refunded tranche no longer blocks the next one.
+    client.approve_milestone(&id, &1u32);
+    assert_eq!(
+        client.try_refund_milestone(&id, &1u32),
+        Err(Ok(EscrowError::InvalidState))
+    );
+
+    // Released tranches stay claimable past their deadline.
+    env.ledger().with_mut(|l| l.timestamp = 500);
+    client.claim(&id, &recipient);
+    assert_eq!(token.balance(&recipient), 600i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_create_milestones_rejects_invalid_tranches() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((recipient.clone(), BPS_DENOMINATOR));
+
+    let options = EscrowOptions::default();
+    let none = Vec::new(&env);
+    let result = client.try_create_milestones(&sender, &recipients, &token_id, &none, &options);
+    assert_eq!(result, Err(Ok(EscrowError::NoMilestones)));
+
+    let milestones = vec![&env, (100i128, 0u64), (0i128, 0u64)];
+    let result =
+        client.try_create_milestones(&sender, &recipients, &token_id, &milestones, &options);
+    assert_eq!(result, Err(Ok(EscrowError::NonPositiveAmount)));
+
+    let milestones = vec![&env, (i128::MAX, 0u64), (1i128, 0u64)];
+    let result =
+        client.try_create_milestones(&sender, &recipients, &token_id, &milestones, &options);
+    assert_eq!(result, Err(Ok(EscrowError::Overflow)));
+}
+
+#[test]
+fn test_dispute_freezes_escrow_until_arbiter_resolves() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let arbiter = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+
+    let mut recipients: Vec<(Address, u32)> = Vec::new(&env);
+    recipients.push_back((recipient.clone(), BPS_DENOMINATOR));
+    let options = EscrowOptions { arbiter: Some(arbiter.clone()), ..Default::default() };
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &100u64, &options);
+    client.approve(&id, &sender);
+
+    client.raise_dispute(&id, &recipient);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Disputed);
+    assert_eq!(client.try_claim(&id, &recipient), Err(Ok(EscrowError::Disputed)));
+    env.ledger().with_mut(|l| l.timestamp = 101);
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::Disputed)));
+
+    let payout = vec![&env, (sender.clone(), 3_000u32), (recipient.clone(), 7_000u32)];
+    client.resolve(&id, &payout);
+    assert_eq!(env.auths()[0].0, arbiter);
+
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 700i128);
+    assert_eq!(token.balance(&recipient), 700i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Resolved);
+
+    env.ledger().with_mut(|l| l.timestamp = 50);
+    assert_eq!(client.try_claim(&id, &recipient), Err(Ok(EscrowError::InvalidState)));
+}
+
+#[test]
+fn test_dispute_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let arbiter = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let plain = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(client.try_raise_dispute(&plain, &sender), Err(Ok(EscrowError::NoArbiter)));
+
+    let mut shares: Vec<(Address, u32)> = Vec::new(&env);
+    shares.push_back((recipient.clone(), BPS_DENOMINATOR));
+    for party in [&sender, &recipient] {
+        let options = EscrowOptions { arbiter: Some(party.clone()), ..Default::default() };
+        assert_eq!(
+            client.try_create_v2(&sender, &shares, &1_000i128, &token_id, &0u64, &options),
+            Err(Ok(EscrowError::InvalidArbiter))
+        );
+    }
+    let options = EscrowOptions { arbiter: Some(arbiter.clone()), ..Default::default() };
+    let id = client.create_v2(&sender, &shares, &1_000i128, &token_id, &0u64, &options);
+
+    let payout = vec![&env, (sender.clone(), BPS_DENOMINATOR)];
+    assert_eq!(client.try_resolve(&id, &payout), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(
+        client.try_raise_dispute(&id, &stranger),
+        Err(Ok(EscrowError::NotParticipant))
+    );
+
+    client.raise_dispute(&id, &sender);
+    assert_eq!(client.try_raise_dispute(&id, &sender), Err(Ok(EscrowError::Disputed)));
+
+    let to_stranger = vec![&env, (stranger.clone(), BPS_DENOMINATOR)];
+    assert_eq!(
+        client.try_resolve(&id, &to_stranger),
+        Err(Ok(EscrowError::NotParticipant))
+    );
+}
+
+#[test]
+fn test_resolve_milestone_escrow_pays_only_locked_funds() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let a = Address::generate(&env);
+    let b = Address::generate(&env);
+    let arbiter = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+
+    let recipients = vec![&env, (a.clone(), 5_000u32), (b.clone(), 5_000u32)];
+    let milestones = vec![&env, (200i128, 0u64), (800i128, 0u64)];
+    let options = EscrowOptions { arbiter: Some(arbiter), ..Default::default() };
+
+    let id = client.create_milestones(&sender, &recipients, &token_id, &milestones, &options);
+    client.approve_milestone(&id, &0u32);
+    client.claim(&id, &a);
+
+    client.raise_dispute(&id, &b);
+    assert_eq!(
+        client.try_approve_milestone(&id, &1u32),
+        Err(Ok(EscrowError::Disputed))
+    );
+
+    // b's unclaimed 100 from the first tranche plus the unreleased 800.
+    client.resolve(&id, &vec![&env, (b.clone(), BPS_DENOMINATOR)]);
+    assert_eq!(token.balance(&a), 100i128);
+    assert_eq!(token.balance(&b), 900i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+}
+
+#[test]
+fn test_claim_withholds_protocol_fee_for_treasury() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let admin = client.get_admin();
+    let treasury = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    client.set_fee_config(&250u32, &treasury, &false);
+
+    let id = client.create(&sender, &recipients, &1_000_000i128, &token_id, &0u64);
+    client.approve(&id, &sender);
+    client.claim(&id, &recipient);
+
+    assert_eq!(token.balance(&recipient), 975_000i128);
+    assert_eq!(client.get_accrued_fees(&token_id), 25_000i128);
+    assert_eq!(token.balance(&contract_id), 25_000i128);
+
+    assert_eq!(client.withdraw_fees(&token_id), 25_000i128);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(token.balance(&treasury), 25_000i128);
+    assert_eq!(client.get_accrued_fees(&token_id), 0i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+}
+
+#[test]
+fn test_refund_fee_is_optional() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let treasury = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    client.set_fee_config(&100u32, &treasury, &false);
+
+    let id = client.create(&sender, &recipients, &10_000i128, &token_id, &0u64);
+    client.refund(&id);
+    assert_eq!(token.balance(&sender), 10_000_000i128);
+    assert_eq!(client.get_accrued_fees(&token_id), 0i128);
+
+    client.set_fee_config(&100u32, &treasury, &true);
+    let id = client.create(&sender, &recipients, &10_000i128, &token_id, &0u64);
+    client.refund(&id);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 100i128);
+    assert_eq!(client.get_accrued_fees(&token_id), 100i128);
+}
+
+#[test]
+fn test_fee_terms_are_fixed_when_the_escrow_is_created() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let treasury = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    client.set_fee_config(&250u32, &treasury, &false);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("feeset")),
+        FeeConfig { fee_bps: 250, treasury: treasury.clone(), fee_on_refund: false },
+    );
+
+    let old = client.create(&sender, &recipients, &10_000i128, &token_id, &0u64);
+    let refunded = client.create(&sender, &recipients, &10_000i128, &token_id, &0u64);
+    assert_eq!(client.get_escrow(&old).fee_bps, 250);
+
+    client.set_fee_config(&MAX_FEE_BPS, &treasury, &true);
+    let new = client.create(&sender, &recipients, &10_000i128, &token_id, &0u64);
+    assert_eq!(client.get_escrow(&new).fee_bps, MAX_FEE_BPS);
+    assert!(client.get_escrow(&new).refund_fee);
+
+    client.approve(&old, &sender);
+    client.claim(&old, &recipient);
+    assert_eq!(token.balance(&recipient), 10_000i128 - 250i128);
+
+    client.refund(&refunded);
+    assert_eq!(client.get_accrued_fees(&token_id), 250i128);
+
+    client.refund(&new);
+    let new_fee = 10_000i128 * MAX_FEE_BPS as i128 / BPS_DENOMINATOR as i128;
+    assert_eq!(client.get_accrued_fees(&token_id), 250i128 + new_fee);
+}
+
+#[test]
+fn test_fee_config_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let (_, client) = deploy_escrow_contract(&env, &admin);
+
+    let treasury = Address::generate(&env);
+    let (token_id, _) = create_unlisted_token(&env, &admin);
+
+    assert_eq!(
+        client.try_set_fee_config(&100u32, &treasury, &false),
+        Err(Ok(EscrowError::NotInitialized))
+    );
+
+    client.initialize(&admin, &admin, &salt(&env));
+    assert_eq!(
+        client.try_initialize(&admin, &admin, &salt(&env)),
+        Err(Ok(EscrowError::AlreadyInitialized))
+    );
+    assert_eq!(
+        client.try_set_fee_config(&(MAX_FEE_BPS + 1), &treasury, &false),
+        Err(Ok(EscrowError::FeeTooHigh))
+    );
+    assert_eq!(client.try_withdraw_fees(&token_id), Err(Ok(EscrowError::NotInitialized)));
+    assert_eq!(client.get_fee_config(), None);
+}
+
+#[test]
+fn test_two_step_admin_transfer() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let (contract_id, client) = deploy_escrow_contract(&env, &admin);
+
+    let candidate = Address::generate(&env);
+
+    assert_eq!(client.try_get_admin(), Err(Ok(EscrowError::NotInitialized)));
+    client.initialize(&admin, &admin, &salt(&env));
+    assert_eq!(client.try_accept_admin(), Err(Ok(EscrowError::NoPendingAdmin)));
+
+    client.propose_admin(&candidate);
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(client.get_admin(), admin);
+
+    client.accept_admin();
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("admset")),
+        (admin.clone(), candidate.clone()),
+    );
+    assert_eq!(env.auths()[0].0, candidate);
+    assert_eq!(client.get_admin(), candidate);
+
+    assert_eq!(client.try_accept_admin(), Err(Ok(EscrowError::NoPendingAdmin)));
+}
+
+#[test]
+fn test_only_the_deployer_can_initialize() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let deployer = Address::generate(&env);
+    let (_, client) = deploy_escrow_contract(&env, &deployer);
+
+    let admin = Address::generate(&env);
+    let intruder = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_unlisted_token(&env, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    assert_eq!(
+        client.try_create(&sender, &recipients, &1_000i128, &token_id, &0u64),
+        Err(Ok(EscrowError::NotInitialized))
+    );
+    assert_eq!(
+        client.try_initialize(&intruder, &intruder, &salt(&env)),
+        Err(Ok(EscrowError::NotDeployer))
+    );
+
+    client.initialize(&admin, &deployer, &salt(&env));
+    assert_eq!(env.auths()[0].0, deployer);
+    assert_eq!(client.get_admin(), admin);
+
+    client.set_token(&token_id, &TokenLimits::default());
+    assert_eq!(client.create(&sender, &recipients, &1_000i128, &token_id, &0u64), 1u64);
+}
+
+#[test]
+fn test_fee_manager_role_replaces_admin_for_fee_config() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let admin = client.get_admin();
+    let fee_manager = Address::generate(&env);
+    let treasury = Address::generate(&env);
+
+    client.set_fee_config(&100u32, &treasury, &false);
+    assert_eq!(env.auths()[0].0, admin);
+
+    client.set_role(&Role::FeeManager, &Some(fee_manager.clone()));
+    assert_eq!(client.get_role(&Role::FeeManager), Some(fee_manager.clone()));
+    assert_eq!(client.get_role(&Role::Pauser), None);
+
+    client.set_fee_config(&200u32, &treasury, &false);
+    assert_eq!(env.auths()[0].0, fee_manager);
+    assert_eq!(client.get_fee_config().unwrap().fee_bps, 200u32);
+
+    client.set_role(&Role::FeeManager, &None);
+    client.set_fee_config(&300u32, &treasury, &false);
+    assert_eq!(env.auths()[0].0, admin);
+}
+
+#[test]
+fn test_pause_flags_block_actions_independently() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let pauser = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    client.set_role(&Role::Pauser, &Some(pauser.clone()));
+    let approved = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    let pending = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    client.approve(&approved, &sender);
+
+    client.set_pause(&PauseFlags { creation: true, ..Default::default() });
+    assert_eq!(env.auths()[0].0, pauser);
+    assert_eq!(
+        client.try_create(&sender, &recipients, &1_000i128, &token_id, &0u64),
+        Err(Ok(EscrowError::Paused))
+    );
+
+    client.set_pause(&PauseFlags { claims: true, ..Default::default() });
+    client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(client.try_claim(&approved, &recipient), Err(Ok(EscrowError::Paused)));
+    client.refund(&pending);
+
+    client.set_pause(&PauseFlags { refunds: true, ..Default::default() });
+    assert_eq!(client.try_refund(&approved), Err(Ok(EscrowError::Paused)));
+    client.claim(&approved, &recipient);
+    assert_eq!(token.balance(&recipient), 1_000i128);
+
+    client.set_pause(&PauseFlags::default());
+    assert_eq!(client.get_pause(), PauseFlags::default());
+}
+
+#[test]
+fn test_expired_refunds_stay_available_while_paused() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let admin = client.get_admin();
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let expiring = client.create(&sender, &recipients, &1_000i128, &token_id, &200u64);
+    let open_ended = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+
+    let flags = PauseFlags {
+        refunds: true,
+        expired_refunds_exempt: true,
+        ..Default::default()
+    };
+    client.set_pause(&flags);
+    assert_last_event(&env, &contract_id, (symbol_short!("escrow"), symbol_short!("pause")), flags);
+    assert_eq!(env.auths()[0].0, admin);
+
+    assert_eq!(client.try_refund(&expiring), Err(Ok(EscrowError::DeadlineNotPassed)));
+    assert_eq!(client.try_refund(&open_ended), Err(Ok(EscrowError::Paused)));
+
+    env.ledger().with_mut(|li| li.timestamp = 201);
+    client.refund(&expiring);
+    assert_eq!(client.get_escrow(&expiring).status, EscrowStatus::Refunded);
+    assert_eq!(token.balance(&contract_id), 1_000i128);
+}
+
+#[test]
+fn test_upgrade_and_migrate_live_contract() {
+    // A snapshot would embed the whole uploaded wasm.
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let admin = client.get_admin();
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+
+    // A first-release deployment: instance-storage records, no schema version.
+    let count = MIGRATION_BATCH_SIZE + 2;
+    token.transfer(&sender, &contract_id, &(1_000i128 * count as i128));
+    let legacy = LegacyEscrowData {
+        sender: sender.clone(),
+        recipients: single_recipient(&env, &recipient),
+        amount: 1_000i128,
+        token: token_id.clone(),
+        created_at: 0,
+        deadline: 0,
+        approved: true,
+        status: 1,
+    };
+    let mut ids: Vec<u64> = Vec::new(&env);
+    env.as_contract(&contract_id, || {
+        for id in 1..=count {
+            env.storage().instance().set(&DataKey::Escrow(id), &legacy);
+            ids.push_back(id);
+        }
+        env.storage().instance().set(&DataKey::NextId, &count);
+        env.storage().instance().set(&DataKey::UserCreated(sender.clone()), &ids);
+        env.storage().instance().set(&DataKey::UserReceived(recipient.clone()), &ids);
+    });
+
+    assert_eq!(client.get_schema_version(), LEGACY_SCHEMA_VERSION);
+
+    // From here on every call runs the uploaded release build.
+    let wasm_hash = env.deployer().upload_contract_wasm(ESCROW_WASM);
+    client.upgrade(&wasm_hash);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("upgraded")),
+        wasm_hash,
+    );
+    assert_eq!(env.auths()[0].0, admin);
+
+    // Each batch must fit the budget of a single transaction.
+    env.cost_estimate().budget().reset_default();
+    assert!(!client.migrate());
+    assert_eq!(client.get_schema_version(), LEGACY_SCHEMA_VERSION);
+    env.cost_estimate().budget().reset_default();
+    assert!(client.migrate());
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+    env.cost_estimate().budget().reset_unlimited();
+    assert!(client.migrate());
+
+    env.as_contract(&contract_id, || {
+        for id in 1..=count {
+            assert!(!env.storage().instance().has(&DataKey::Escrow(id)));
+            assert!(env.storage().persistent().has(&DataKey::Escrow(id)));
+        }
+        assert!(!env.storage().instance().has(&DataKey::UserCreated(sender.clone())));
+        assert!(!env.storage().instance().has(&DataKey::UserReceived(recipient.clone())));
+        assert!(env.storage().persistent().has(&DataKey::UserCreatedCount(sender.clone())));
+        assert!(env.storage().persistent().has(&DataKey::UserReceivedCount(recipient.clone())));
+    });
+    assert_eq!(client.get_created_ids(&sender), ids);
+    assert_eq!(client.get_received_ids(&recipient), ids);
+    assert_eq!(client.get_locked(&token_id), 1_000i128 * count as i128);
+
+    client.claim(&count, &recipient);
+    assert_eq!(token.balance(&recipient), 1_000i128);
+    assert_eq!(client.get_escrow(&1u64).status, EscrowStatus::Approved);
+
+    let recipients = single_recipient(&env, &recipient);
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(id, count + 1);
+}
+
+#[test]
+fn test_migrate_skips_unreadable_records() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    token.transfer(&sender, &contract_id, &2_000i128);
+
+    let mut legacy = LegacyEscrowData {
+        sender: sender.clone(),
+        recipients: single_recipient(&env, &recipient),
+        amount: 1_000i128,
+        token: token_id.clone(),
+        created_at: 0,
+        deadline: 0,
+        approved: true,
+        status: 1,
+    };
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&DataKey::Escrow(1), &legacy);
+        legacy.status = 9;
+        env.storage().instance().set(&DataKey::Escrow(2), &legacy);
+        legacy.status = 1;
+        env.storage().instance().set(&DataKey::Escrow(3), &legacy);
+        env.storage().instance().set(&DataKey::NextId, &3u64);
+    });
+
+    assert!(client.migrate());
+    // Reported just before the closing `migrated` event.
+    let events = env.events().all();
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+
+    assert_eq!(
+        events.slice(events.len() - 2..events.len() - 1),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("escrow"), symbol_short!("migskip"), 2u64).into_val(&env),
+                EscrowError::InvalidState.into_val(&env),
+            ),
+        ]
+    );
+
+    env.as_contract(&contract_id, || {
+        assert!(env.storage().persistent().has(&DataKey::Escrow(1)));
+        assert!(env.storage().instance().has(&DataKey::Escrow(2)));
+        assert!(env.storage().persistent().has(&DataKey::Escrow(3)));
+    });
+    assert_eq!(client.get_locked(&token_id), 2_000i128);
+}
+
+#[test]
+fn test_fresh_contract_starts_at_current_schema() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let (_, client) = deploy_escrow_contract(&env, &admin);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+    assert_eq!(client.try_migrate(), Err(Ok(EscrowError::NotInitialized)));
+    client.initialize(&admin, &admin, &salt(&env));
+
+    let (token_id, _) = create_token(&env, &client, &sender);
+    client.create(&sender, &single_recipient(&env, &recipient), &1_000i128, &token_id, &0u64);
+    assert_eq!(client.get_schema_version(), SCHEMA_VERSION);
+    assert!(client.migrate());
+}
+
+#[test]
+fn test_create_requires_allowlisted_token_within_limits() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let admin = client.get_admin();
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_unlisted_token(&env, &sender);
+    let (other_token_id, _) = create_unlisted_token(&env, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    assert_eq!(
+        client.try_create(&sender, &recipients, &1_000i128, &token_id, &0u64),
+        Err(Ok(EscrowError::TokenNotAllowed))
+    );
+
+    let limits = TokenLimits { min_amount: Some(100i128), max_amount: Some(5_000i128) };
+    client.set_token(&token_id, &limits);
+    assert_eq!(env.auths()[0].0, admin);
+    client.set_token(&other_token_id, &TokenLimits::default());
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("tokenset"), other_token_id.clone()),
+        TokenLimits::default(),
+    );
+    assert_eq!(
+        client.get_supported_tokens(),
+        vec![&env, token_id.clone(), other_token_id.clone()]
+    );
+    assert_eq!(client.get_token_limits(&token_id), Some(limits.clone()));
+
+    assert_eq!(
+        client.try_create(&sender, &recipients, &99i128, &token_id, &0u64),
+        Err(Ok(EscrowError::AmountBelowMin))
+    );
+    assert_eq!(
+        client.try_create(&sender, &recipients, &5_001i128, &token_id, &0u64),
+        Err(Ok(EscrowError::AmountAboveMax))
+    );
+    let id = client.create(&sender, &recipients, &5_000i128, &token_id, &0u64);
+    client.create(&sender, &recipients, &1_000_000i128, &other_token_id, &0u64);
+
+    client.remove_token(&token_id);
+    assert_eq!(client.get_supported_tokens(), vec![&env, other_token_id.clone()]);
+    assert_eq!(client.get_token_limits(&token_id), None);
+    assert_eq!(
+        client.try_create(&sender, &recipients, &1_000i128, &token_id, &0u64),
+        Err(Ok(EscrowError::TokenNotAllowed))
+    );
+
+    // Delisting does not strand escrows already holding the token.
+    client.approve(&id, &sender);
+    client.claim(&id, &recipient);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_token_allowlist_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let (_, client) = deploy_escrow_contract(&env, &admin);
+
+    let token_id = Address::generate(&env);
+
+    assert_eq!(
+        client.try_set_token(&token_id, &TokenLimits::default()),
+        Err(Ok(EscrowError::NotInitialized))
+    );
+    client.initialize(&admin, &admin, &salt(&env));
+
+    let inverted = TokenLimits { min_amount: Some(10i128), max_amount: Some(9i128) };
+    assert_eq!(client.try_set_token(&token_id, &inverted), Err(Ok(EscrowError::BadTokenLimits)));
+    let negative = TokenLimits { min_amount: Some(-1i128), max_amount: None };
+    assert_eq!(
+        client.try_set_token(&token_id, &negative),
+        Err(Ok(EscrowError::NonPositiveAmount))
+    );
+    assert_eq!(client.try_remove_token(&token_id), Err(Ok(EscrowError::TokenNotAllowed)));
+}
+
+#[test]
+fn test_create_records_amount_received_from_fee_on_transfer_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_fee_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(client.get_escrow(&id).amount, 990i128);
+    assert_eq!(token.balance(&contract_id), 990i128);
+    assert_eq!(client.get_locked(&token_id), 990i128);
+
+    client.approve(&id, &sender);
+    client.claim(&id, &recipient);
+    assert_eq!(token.balance(&recipient), 981i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_locked(&token_id), 0i128);
+
+    let mut milestones: Vec<(i128, u64)> = Vec::new(&env);
+    milestones.push_back((1_000i128, 0u64));
+    assert_eq!(
+        client.try_create_milestones(
+            &sender,
+            &vec![&env, (recipient.clone(), BPS_DENOMINATOR)],
+            &token_id,
+            &milestones,
+            &EscrowOptions::default(),
+        ),
+        Err(Ok(EscrowError::DepositShortfall))
+    );
+}
+
+#[test]
+fn test_locked_funds_never_exceed_held_balance() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_fee_token(&env, &client, &sender);
+    let (plain_token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    let plain = client.create(&sender, &recipients, &2_000i128, &plain_token_id, &0u64);
+    assert_eq!(client.get_locked(&plain_token_id), 2_000i128);
+
+    token.rebase_down(&contract_id, &500i128);
+    assert_eq!(
+        client.try_create(&sender, &recipients, &1_000i128, &token_id, &0u64),
+        Err(Ok(EscrowError::Undercollateralized))
+    );
+    assert_eq!(client.get_locked(&token_id), 990i128);
+
+    client.create(&sender, &recipients, &1_000i128, &plain_token_id, &0u64);
+    client.refund(&plain);
+    assert_eq!(client.get_locked(&plain_token_id), 1_000i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Pending);
+}
+
+#[test]
+fn test_legacy_escrows_are_counted_as_locked_once_upgraded() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    let id = client.create(&sender, &recipients, &500i128, &token_id, &0u64);
+    token.transfer(&sender, &contract_id, &1_000i128);
+    let legacy = LegacyEscrowData {
+        sender: sender.clone(),
+        recipients: recipients.clone(),
+        amount: 1_000i128,
+        token: token_id.clone(),
+        created_at: 0,
+        deadline: 0,
+        approved: true,
+        status: 1,
+    };
+    env.as_contract(&contract_id, || {
+        env.storage().instance().set(&DataKey::Escrow(2), &legacy);
+        env.storage().instance().set(&DataKey::NextId, &2u64);
+    });
+    assert_eq!(client.get_locked(&token_id), 500i128);
+
+    client.get_escrow(&2u64);
+    assert_eq!(client.get_locked(&token_id), 1_500i128);
+    client.get_escrow(&2u64);
+    assert_eq!(client.get_locked(&token_id), 1_500i128);
+
+    client.claim(&2u64, &recipient);
+    assert_eq!(client.get_locked(&token_id), 500i128);
+    assert_eq!(token.balance(&contract_id), 500i128);
+
+    client.approve(&id, &sender);
+    client.claim(&id, &recipient);
+    assert_eq!(client.get_locked(&token_id), 0i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+}
+
+#[test]
+fn test_finished_legacy_escrows_are_not_counted_as_locked() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    // Claimed and refunded, the only finished statuses of the legacy layout.
+    env.as_contract(&contract_id, || {
+        for (id, status) in [(1u64, 2u32), (2u64, 3u32)] {
+            let legacy = LegacyEscrowData {
+                sender: sender.clone(),
+                recipients: recipients.clone(),
+                amount: 1_000i128,
+                token: token_id.clone(),
+                created_at: 0,
+                deadline: 0,
+                approved: status == 2,
+                status,
+            };
+            env.storage().instance().set(&DataKey::Escrow(id), &legacy);
+        }
+        env.storage().instance().set(&DataKey::NextId, &2u64);
+    });
+
+    assert_eq!(client.get_escrow(&1u64).status, EscrowStatus::Claimed);
+    assert_eq!(client.get_escrow(&2u64).status, EscrowStatus::Refunded);
+    assert_eq!(client.get_locked(&token_id), 0i128);
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(id, 3u64);
+    assert_eq!(client.get_locked(&token_id), 1_000i128);
+}
+
+#[test]
+fn test_deposit_more_and_mutual_reduce_update_amount() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 60u32), (second.clone(), 40u32)];
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    client.deposit_more(&id, &500i128);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("deposit"), id),
+        (500i128, 1_500i128),
+    );
+    assert_eq!(env.auths()[0].0, sender);
+    assert_eq!(client.get_escrow(&id).amount, 1_500i128);
+    assert_eq!(client.get_locked(&token_id), 1_500i128);
+
+    client.approve(&id, &sender);
+    client.reduce(&id, &300i128);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("reduced"), id),
+        (300i128, 1_200i128),
+    );
+    assert_eq!(signers(&env), [sender.clone(), first.clone(), second.clone()]);
+    assert_eq!(client.get_escrow(&id).amount, 1_200i128);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 1_200i128);
+
+    client.claim(&id, &first);
+    client.claim(&id, &second);
+    assert_eq!(token.balance(&first), 720i128);
+    assert_eq!(token.balance(&second), 480i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_locked(&token_id), 0i128);
+}
+
+#[test]
+fn test_amount_adjustment_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 50u32), (second.clone(), 50u32)];
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(client.try_deposit_more(&id, &0i128), Err(Ok(EscrowError::NonPositiveAmount)));
+    assert_eq!(client.try_reduce(&id, &-1i128), Err(Ok(EscrowError::NonPositiveAmount)));
+    assert_eq!(client.try_reduce(&id, &1_000i128), Err(Ok(EscrowError::ExceedsEscrow)));
+
+    client.approve(&id, &sender);
+    client.claim(&id, &first);
+    assert_eq!(client.try_deposit_more(&id, &100i128), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(client.try_reduce(&id, &100i128), Err(Ok(EscrowError::InvalidState)));
+
+    let mut milestones: Vec<(i128, u64)> = Vec::new(&env);
+    milestones.push_back((1_000i128, 0u64));
+    let staged = client.create_milestones(
+        &sender,
+        &vec![&env, (first.clone(), BPS_DENOMINATOR)],
+        &token_id,
+        &milestones,
+        &EscrowOptions::default(),
+    );
+    assert_eq!(client.try_deposit_more(&staged, &100i128), Err(Ok(EscrowError::InvalidState)));
+}
+
+#[test]
+fn test_deadline_extension_and_shortening() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 50u32), (second.clone(), 50u32)];
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &200u64);
+
+    client.extend_deadline(&id, &500u64);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("deadline"), id),
+        (200u64, 500u64),
+    );
+    assert_eq!(env.auths().len(), 1);
+    assert_eq!(env.auths()[0].0, sender);
+    assert_eq!(client.get_escrow(&id).deadline, 500u64);
+
+    client.shorten_deadline(&id, &300u64);
+    assert_eq!(signers(&env), [first.clone(), second.clone()]);
+    assert_eq!(client.get_escrow(&id).deadline, 300u64);
+
+    env.ledger().with_mut(|li| li.timestamp = 301);
+    client.refund(&id);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Refunded);
+}
+
+#[test]
+fn test_deadline_validation_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = single_recipient(&env, &recipient);
+
+    assert_eq!(
+        client.try_create(&sender, &recipients, &1_000i128, &token_id, &100u64),
+        Err(Ok(EscrowError::InvalidDeadline))
+    );
+    let mut milestones: Vec<(i128, u64)> = Vec::new(&env);
+    milestones.push_back((1_000i128, 50u64));
+    assert_eq!(
+        client.try_create_milestones(
+            &sender,
+            &vec![&env, (recipient.clone(), BPS_DENOMINATOR)],
+            &token_id,
+            &milestones,
+            &EscrowOptions::default(),
+        ),
+        Err(Ok(EscrowError::InvalidDeadline))
+    );
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &200u64);
+    assert_eq!(client.try_extend_deadline(&id, &200u64), Err(Ok(EscrowError::InvalidDeadline)));
+    assert_eq!(client.try_shorten_deadline(&id, &250u64), Err(Ok(EscrowError::InvalidDeadline)));
+    assert_eq!(client.try_shorten_deadline(&id, &100u64), Err(Ok(EscrowError::InvalidDeadline)));
+
+    let open_ended = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(
+        client.try_extend_deadline(&open_ended, &300u64),
+        Err(Ok(EscrowError::InvalidState))
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 201);
+    assert_eq!(client.try_extend_deadline(&id, &300u64), Err(Ok(EscrowError::DeadlinePassed)));
+}
+
+#[test]
+fn test_mutual_cancel_refunds_before_deadline() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 50u32), (second.clone(), 50u32)];
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &1_000u64);
+    client.approve(&id, &sender);
+    client.claim(&id, &first);
+
+    client.cancel(&id);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("cancelled"), id),
+        (sender.clone(), 500i128),
+    );
+    assert_eq!(signers(&env), [sender.clone(), first.clone(), second.clone()]);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Cancelled);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 500i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+
+    assert_eq!(client.try_cancel(&id), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(client.try_claim(&id, &second), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::InvalidState)));
+}
+
+#[test]
+fn test_cancel_is_blocked_while_disputed() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let arbiter = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let options = EscrowOptions { arbiter: Some(arbiter), ..Default::default() };
+
+    let id = client.create_v2(
+        &sender,
+        &vec![&env, (recipient.clone(), BPS_DENOMINATOR)],
+        &1_000i128,
+        &token_id,
+        &0u64,
+        &options,
+    );
+    client.raise_dispute(&id, &recipient);
+    assert_eq!(client.try_cancel(&id), Err(Ok(EscrowError::Disputed)));
+}
+
+#[test]
+fn test_partial_waivers_renormalize_remaining_shares() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let third = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![
+        &env,
+        (first.clone(), 40u32),
+        (second.clone(), 40u32),
+        (third.clone(), 20u32),
+    ];
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    client.waive(&id, &third);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("waived"), id),
+        (third.clone(), 200i128),
+    );
+    assert_eq!(env.auths()[0].0, third);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 800i128);
+
+    let data = client.get_escrow(&id);
+    assert_eq!(data.amount, 800i128);
+    assert_eq!(data.recipients, vec![&env, (first.clone(), 5_000u32), (second.clone(), 5_000u32)]);
+    assert_eq!(data.claimed, vec![&env, false, false]);
+
+    client.approve(&id, &sender);
+    client.waive(&id, &first);
+    assert_eq!(client.get_escrow(&id).recipients, vec![&env, (second.clone(), BPS_DENOMINATOR)]);
+
+    client.claim(&id, &second);
+    assert_eq!(token.balance(&second), 400i128);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 400i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_escrow_is_refunded_once_every_recipient_waives() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 50u32), (second.clone(), 50u32)];
+
+    let id = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(client.try_waive(&id, &sender), Err(Ok(EscrowError::NotRecipient)));
+
+    client.waive(&id, &first);
+    client.waive(&id, &second);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("refunded"), id),
+        sender.clone(),
+    );
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Refunded);
+    assert_eq!(token.balance(&sender), 10_000_000i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+
+    assert_eq!(client.try_waive(&id, &second), Err(Ok(EscrowError::InvalidState)));
+
+    let claimed = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    client.approve(&claimed, &sender);
+    client.claim(&claimed, &first);
+    assert_eq!(client.try_waive(&claimed, &second), Err(Ok(EscrowError::InvalidState)));
+}
+
+#[test]
+fn test_acceptance_required_before_approval() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 5_000u32), (second.clone(), 5_000u32)];
+    let options = EscrowOptions { require_acceptance: true, ..Default::default() };
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+    assert_eq!(client.get_escrow(&id).accepted, vec![&env, false, false]);
+
+    client.accept(&id, &first);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("accepted"), id),
+        first.clone(),
+    );
+    assert_eq!(env.auths()[0].0, first);
+    assert_eq!(client.try_approve(&id, &sender), Err(Ok(EscrowError::NotAccepted)));
+    assert_eq!(client.try_accept(&id, &first), Err(Ok(EscrowError::InvalidState)));
+
+    client.accept(&id, &second);
+    client.approve(&id, &sender);
+    client.claim(&id, &first);
+    assert_eq!(token.balance(&first), 500i128);
+
+    let single = single_recipient(&env, &first);
+    let open = client.create(&sender, &single, &1_000i128, &token_id, &0u64);
+    assert_eq!(client.try_accept(&open, &first), Err(Ok(EscrowError::InvalidState)));
+    client.approve(&open, &sender);
+}
+
+#[test]
+fn test_reject_refunds_sender_and_drops_received_index_entry() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 5_000u32), (second.clone(), 5_000u32)];
+    let options = EscrowOptions { require_acceptance: true, ..Default::default() };
+
+    let before = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+    let after = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+    client.accept(&id, &first);
+    assert_eq!(client.try_reject(&id, &first), Err(Ok(EscrowError::InvalidState)));
+
+    client.reject(&id, &second);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("rejected"), id),
+        (second.clone(), 1_000i128),
+    );
+    assert_eq!(env.auths()[0].0, second);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Refunded);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 2_000i128);
+    assert_eq!(token.balance(&contract_id), 2_000i128);
+
+    assert_eq!(client.get_received_ids(&second), vec![&env, before, after]);
+    assert_eq!(client.get_received_ids(&first), vec![&env, before, id, after]);
+    assert_eq!(client.get_created_ids(&sender), vec![&env, before, id, after]);
+    assert_eq!(client.try_accept(&id, &second), Err(Ok(EscrowError::InvalidState)));
+}
+
+#[test]
+fn test_htlc_pays_recipient_on_matching_preimage() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let preimage = BytesN::from_array(&env, &[7u8; 32]);
+    let hashlock = env.crypto().sha256(&preimage.clone().into()).to_bytes();
+
+    let id = client.create_htlc(&sender, &recipient, &1_000i128, &token_id, &hashlock, &200u64);
+    assert_eq!(client.get_escrow(&id).hashlock, Some(hashlock.clone()));
+    assert_eq!(client.try_approve(&id, &sender), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(client.try_claim(&id, &recipient), Err(Ok(EscrowError::NotApproved)));
+    assert_eq!(
+        client.try_claim_with_preimage(&id, &BytesN::from_array(&env, &[8u8; 32])),
+        Err(Ok(EscrowError::BadPreimage))
+    );
+
+    client.claim_with_preimage(&id, &preimage);
+    let events = env.events().all();
+    assert_eq!(token.balance(&recipient), 1_000i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+
+    assert_eq!(
+        events.slice(events.len() - 2..events.len() - 1),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("escrow"), symbol_short!("preimage"), id).into_val(&env),
+                (recipient.clone(), preimage.clone()).into_val(&env),
+            ),
+        ]
+    );
+    assert_eq!(
+        client.try_claim_with_preimage(&id, &preimage),
+        Err(Ok(EscrowError::InvalidState))
+    );
+}
+
+#[test]
+fn test_htlc_rejects_preimages_that_are_not_32_bytes() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+
+    // A short secret hashes fine, but could not be redeemed on a chain
+    // following BIP-199, so it must not redeem here either.
+    let short = Bytes::from_slice(&env, b"swap secret");
+    let hashlock = env.crypto().sha256(&short).to_bytes();
+    let id = client.create_htlc(&sender, &recipient, &1_000i128, &token_id, &hashlock, &200u64);
+
+    let result = env.try_invoke_contract::<(), soroban_sdk::Error>(
+        &contract_id,
+        &Symbol::new(&env, "claim_with_preimage"),
+        vec![&env, id.into_val(&env), short.into_val(&env)],
+    );
+    assert!(result.is_err());
+    assert_eq!(token.balance(&recipient), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Pending);
+}
+
+#[test]
+fn test_htlc_refunds_after_timelock() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let preimage = BytesN::from_array(&env, &[7u8; 32]);
+    let hashlock = env.crypto().sha256(&preimage.clone().into()).to_bytes();
+
+    assert_eq!(
+        client.try_create_htlc(&sender, &recipient, &1_000i128, &token_id, &hashlock, &0u64),
+        Err(Ok(EscrowError::InvalidDeadline))
+    );
+    let id = client.create_htlc(&sender, &recipient, &1_000i128, &token_id, &hashlock, &200u64);
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::DeadlineNotPassed)));
+
+    env.ledger().with_mut(|li| li.timestamp = 201);
+    assert_eq!(
+        client.try_claim_with_preimage(&id, &preimage),
+        Err(Ok(EscrowError::DeadlinePassed))
+    );
+    client.refund(&id);
+    assert_eq!(token.balance(&sender), 10_000_000i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Refunded);
+
+    let recipients = single_recipient(&env, &recipient);
+    let plain = client.create(&sender, &recipients, &1_000i128, &token_id, &0u64);
+    assert_eq!(
+        client.try_claim_with_preimage(&plain, &preimage),
+        Err(Ok(EscrowError::InvalidState))
+    );
+}
+
+#[test]
+fn test_vesting_streams_linearly_after_cliff() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 6_000u32), (second.clone(), 4_000u32)];
+
+    let id = client.create_vesting(
+        &sender,
+        &recipients,
+        &1_000i128,
+        &token_id,
+        &100u64,
+        &150u64,
+        &200u64,
+        &EscrowOptions::default(),
+    );
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Approved);
+    assert_eq!(client.try_claim(&id, &first), Err(Ok(EscrowError::InvalidState)));
+
+    env.ledger().with_mut(|li| li.timestamp = 149);
+    assert_eq!(client.try_withdraw_vested(&id, &first), Err(Ok(EscrowError::NothingToClaim)));
+
+    env.ledger().with_mut(|li| li.timestamp = 150);
+    client.withdraw_vested(&id, &first);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("vestwd"), id),
+        (first.clone(), 300i128),
+    );
+    assert_eq!(env.auths()[0].0, first);
+    assert_eq!(token.balance(&first), 300i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::PartiallyClaimed);
+
+    env.ledger().with_mut(|li| li.timestamp = 175);
+    client.withdraw_vested(&id, &second);
+    assert_eq!(token.balance(&second), 300i128);
+    assert_eq!(client.get_locked(&token_id), 400i128);
+
+    env.ledger().with_mut(|li| li.timestamp = 250);
+    client.withdraw_vested(&id, &first);
+    client.withdraw_vested(&id, &second);
+    assert_eq!(token.balance(&first), 600i128);
+    assert_eq!(token.balance(&second), 400i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+}
+
+#[test]
+fn test_sender_stops_vesting_and_recovers_unvested_remainder() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let options = EscrowOptions::default();
+
+    let id = client.create_vesting(
+        &sender, &recipients, &1_000i128, &token_id, &100u64, &0u64, &200u64, &options,
+    );
+
+    env.ledger().with_mut(|li| li.timestamp = 125);
+    client.withdraw_vested(&id, &recipient);
+    assert_eq!(token.balance(&recipient), 250i128);
+
+    env.ledger().with_mut(|li| li.timestamp = 150);
+    client.cancel_vesting(&id);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("veststop"), id),
+        (sender.clone(), 500i128),
+    );
+    assert_eq!(env.auths()[0].0, sender);
+    assert_eq!(token.balance(&sender), 10_000_000i128 - 500i128);
+    assert_eq!(client.try_cancel_vesting(&id), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::InvalidState)));
+
+    env.ledger().with_mut(|li| li.timestamp = 300);
+    client.withdraw_vested(&id, &recipient);
+    assert_eq!(token.balance(&recipient), 500i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+
+    // Stopping before anything vested refunds the whole stream.
+    env.ledger().with_mut(|li| li.timestamp = 400);
+    let early = client.create_vesting(
+        &sender, &recipients, &1_000i128, &token_id, &500u64, &0u64, &600u64, &options,
+    );
+    client.cancel_vesting(&early);
+    assert_eq!(client.get_escrow(&early).status, EscrowStatus::Refunded);
+    assert_eq!(token.balance(&contract_id), 0i128);
+}
+
+#[test]
+fn test_create_vesting_rejects_bad_schedules() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let options = EscrowOptions::default();
+
+    let cases = [
+        ((200u64, 0u64, 200u64), EscrowError::BadSchedule),
+        ((200u64, 150u64, 300u64), EscrowError::BadSchedule),
+        ((200u64, 350u64, 300u64), EscrowError::BadSchedule),
+        ((10u64, 0u64, 90u64), EscrowError::InvalidDeadline),
+    ];
+    for ((start, cliff, end), error) in cases {
+        assert_eq!(
+            client.try_create_vesting(
+                &sender, &recipients, &1_000i128, &token_id, &start, &cliff, &end, &options,
+            ),
+            Err(Ok(error))
+        );
+    }
+
+    let dust = vec![&env, (recipient.clone(), 1u32), (Address::generate(&env), 9_999u32)];
+    assert_eq!(
+        client.try_create_vesting(
+            &sender, &dust, &1i128, &token_id, &100u64, &0u64, &200u64, &options,
+        ),
+        Err(Ok(EscrowError::ZeroShare))
+    );
+
+    let accepting = EscrowOptions { require_acceptance: true, ..Default::default() };
+    assert_eq!(
+        client.try_create_vesting(
+            &sender, &recipients, &1_000i128, &token_id, &100u64, &0u64, &200u64, &accepting,
+        ),
+        Err(Ok(EscrowError::InvalidState))
+    );
+}
+
+#[test]
+fn test_approver_threshold_approves_once_met() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (first, second, third) =
+        (Address::generate(&env), Address::generate(&env), Address::generate(&env));
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let options = EscrowOptions {
+        approvers: Some(vec![&env, first.clone(), second.clone(), third.clone()]),
+        threshold: 2,
+        ..Default::default()
+    };
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+    assert_eq!(client.try_approve(&id, &sender), Err(Ok(EscrowError::NotApprover)));
+
+    client.approve(&id, &first);
+    assert_eq!(env.auths()[0].0, first);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Pending);
+    assert_eq!(client.get_escrow(&id).approvals, vec![&env, true, false, false]);
+    assert_eq!(client.try_approve(&id, &first), Err(Ok(EscrowError::InvalidState)));
+
+    client.revoke_approval(&id, &first);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("revoked"), id),
+        (first.clone(), 0u32),
+    );
+    assert_eq!(env.auths()[0].0, first);
+    assert_eq!(client.get_escrow(&id).approvals, vec![&env, false, false, false]);
+    assert_eq!(client.try_revoke_approval(&id, &first), Err(Ok(EscrowError::InvalidState)));
+
+    client.approve(&id, &second);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Pending);
+    client.approve(&id, &third);
+    let events = env.events().all();
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Approved);
+
+    assert_eq!(
+        events.slice(events.len() - 2..),
+        vec![
+            &env,
+            (
+                contract_id.clone(),
+                (symbol_short!("escrow"), symbol_short!("approval"), id).into_val(&env),
+                (third.clone(), 2u32).into_val(&env),
+            ),
+            (
+                contract_id.clone(),
+                (symbol_short!("escrow"), symbol_short!("approved"), id).into_val(&env),
+                third.clone().into_val(&env),
+            ),
+        ]
+    );
+
+    assert_eq!(client.try_approve(&id, &first), Err(Ok(EscrowError::InvalidState)));
+    assert_eq!(client.try_revoke_approval(&id, &second), Err(Ok(EscrowError::InvalidState)));
+
+    client.claim(&id, &recipient);
+    assert_eq!(token.balance(&recipient), 1_000i128);
+}
+
+#[test]
+fn test_approver_set_validation_errors() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (_, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let approver = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+
+    let cases = [
+        (Some(vec![&env, approver.clone()]), 0u32, EscrowError::BadThreshold),
+        (Some(vec![&env, approver.clone()]), 2u32, EscrowError::BadThreshold),
+        (None, 1u32, EscrowError::BadThreshold),
+        (
+            Some(vec![&env, approver.clone(), approver.clone()]),
+            1u32,
+            EscrowError::DuplicateApprover,
+        ),
+    ];
+    for (approvers, threshold, error) in cases {
+        let options = EscrowOptions { approvers, threshold, ..Default::default() };
+        assert_eq!(
+            client.try_create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options),
+            Err(Ok(error))
+        );
+    }
+
+    let single = single_recipient(&env, &recipient);
+    let id = client.create(&sender, &single, &1_000i128, &token_id, &0u64);
+    assert_eq!(client.try_approve(&id, &approver), Err(Ok(EscrowError::NotApprover)));
+    assert_eq!(client.try_revoke_approval(&id, &sender), Err(Ok(EscrowError::NotApprover)));
+}
+
+#[test]
+fn test_third_party_approver_replaces_sender() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+    let service = env.register(oracle::MockOracle, ());
+    let service_client = oracle::MockOracleClient::new(&env, &service);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let options = EscrowOptions { approver: Some(service.clone()), ..Default::default() };
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+    assert_eq!(client.get_escrow(&id).approver, Some(service.clone()));
+    assert_eq!(client.try_approve(&id, &sender), Err(Ok(EscrowError::NotApprover)));
+
+    // The approving contract authorizes its own call without any signature.
+    env.set_auths(&[]);
+    service_client.confirm_delivery(&contract_id, &id);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("approved"), id),
+        service.clone(),
+    );
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Approved);
+
+    env.mock_all_auths();
+    client.claim(&id, &recipient);
+    assert_eq!(token.balance(&recipient), 1_000i128);
+}
+
+#[test]
+fn test_oracle_fulfillment_lets_anyone_approve() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (contract_id, client) = create_escrow_contract(&env);
+    let oracle_id = env.register(oracle::MockOracle, ());
+    let oracle_client = oracle::MockOracleClient::new(&env, &oracle_id);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let options = EscrowOptions { oracle: Some(oracle_id.clone()), ..Default::default() };
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options);
+    assert_eq!(client.try_approve_via_oracle(&id), Err(Ok(EscrowError::NotFulfilled)));
+
+    oracle_client.set_fulfilled(&id, &true);
+    env.set_auths(&[]);
+    client.approve_via_oracle(&id);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("approved"), id),
+        oracle_id.clone(),
+    );
+    assert_eq!(env.auths(), std::vec![]);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Approved);
+    assert_eq!(client.try_approve_via_oracle(&id), Err(Ok(EscrowError::InvalidState)));
+
+    env.mock_all_auths();
+    let plain = client.create_v2(
+        &sender, &recipients, &1_000i128, &token_id, &0u64, &EscrowOptions::default(),
+    );
+    assert_eq!(client.try_approve_via_oracle(&plain), Err(Ok(EscrowError::InvalidState)));
+
+    let both = EscrowOptions {
+        approvers: Some(vec![&env, Address::generate(&env)]),
+        threshold: 1,
+        approver: Some(Address::generate(&env)),
+        ..Default::default()
+    };
+    assert_eq!(
+        client.try_create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &both),
+        Err(Ok(EscrowError::InvalidState))
+    );
+}
+
+#[test]
+fn test_release_on_expiry_lets_recipients_claim_unapproved() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let first = Address::generate(&env);
+    let second = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (first.clone(), 5_000u32), (second.clone(), 5_000u32)];
+    let options = EscrowOptions { on_expiry: ReleasePolicy::ReleaseOnExpiry, ..Default::default() };
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &500u64, &options);
+    assert_eq!(client.try_claim(&id, &first), Err(Ok(EscrowError::NotApproved)));
+
+    env.ledger().with_mut(|li| li.timestamp = 501);
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::ReleasedOnExpiry)));
+    assert_eq!(client.try_object(&id), Err(Ok(EscrowError::DeadlinePassed)));
+
+    client.claim(&id, &first);
+    assert_eq!(env.auths()[0].0, first);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::PartiallyClaimed);
+    assert_eq!(client.try_refund(&id), Err(Ok(EscrowError::ReleasedOnExpiry)));
+    client.claim(&id, &second);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Claimed);
+    assert_eq!(token.balance(&first), 500i128);
+    assert_eq!(token.balance(&second), 500i128);
+    assert_eq!(token.balance(&contract_id), 0i128);
+
+    let default = client.create_v2(
+        &sender, &recipients, &1_000i128, &token_id, &600u64, &EscrowOptions::default(),
+    );
+    assert_eq!(client.try_object(&default), Err(Ok(EscrowError::InvalidState)));
+    env.ledger().with_mut(|li| li.timestamp = 601);
+    assert_eq!(client.try_claim(&default, &first), Err(Ok(EscrowError::NotApproved)));
+    client.refund(&default);
+}
+
+#[test]
+fn test_objection_before_deadline_restores_refund() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, token) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let options = EscrowOptions { on_expiry: ReleasePolicy::ReleaseOnExpiry, ..Default::default() };
+
+    assert_eq!(
+        client.try_create_v2(&sender, &recipients, &1_000i128, &token_id, &0u64, &options),
+        Err(Ok(EscrowError::InvalidDeadline))
+    );
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &500u64, &options);
+    client.object(&id);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (symbol_short!("escrow"), symbol_short!("objected"), id),
+        sender.clone(),
+    );
+    assert_eq!(env.auths()[0].0, sender);
+    assert!(client.get_escrow(&id).objected);
+    assert_eq!(client.try_object(&id), Err(Ok(EscrowError::InvalidState)));
+
+    env.ledger().with_mut(|li| li.timestamp = 501);
+    assert_eq!(client.try_claim(&id, &recipient), Err(Ok(EscrowError::NotApproved)));
+    client.refund(&id);
+    assert_eq!(client.get_escrow(&id).status, EscrowStatus::Refunded);
+    assert_eq!(token.balance(&sender), 10_000_000i128);
+}
+
+#[test]
+fn test_shortening_release_on_expiry_deadline_needs_sender() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|li| li.timestamp = 100);
+
+    let (contract_id, client) = create_escrow_contract(&env);
+
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+    let (token_id, _) = create_token(&env, &client, &sender);
+    let recipients = vec![&env, (recipient.clone(), BPS_DENOMINATOR)];
+    let options = EscrowOptions { on_expiry: ReleasePolicy::ReleaseOnExpiry, ..Default::default() };
+
+    let id = client.create_v2(&sender, &recipients, &1_000i128, &token_id, &500u64, &options);
+
+    // The recipient alone cannot pull the deadline in and claim unapproved.
+    env.mock_auths(&[MockAuth {
+        address: &recipient,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "shorten_deadline",
+            args: (id, 101u64).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_shorten_deadline(&id, &101u64).is_err());
+    assert_eq!(client.get_escrow(&id).deadline, 500u64);
+
+    env.mock_all_auths();
+    client.shorten_deadline(&id, &101u64);
+    assert_eq!(signers(&env), [recipient.clone(), sender.clone()]);
+    assert_eq!(client.get_escrow(&id).deadline, 101u64);
+}